@@ -1,16 +1,21 @@
 use std::error::Error;
 
 use addin1c::{AddinResult, CStr1C, MethodInfo, PropInfo, SimpleAddin, Variant, name};
-
-use crate::filters;
+use tech_log_parser::{SerializeOptions, filters::Filter, query};
 
 pub struct Parser {
     last_error: Option<Box<dyn Error>>,
+    format: SerializeOptions,
+    format_json: String,
 }
 
 impl Parser {
     pub fn new() -> Self {
-        Self { last_error: None }
+        Self {
+            last_error: None,
+            format: SerializeOptions::default(),
+            format_json: String::new(),
+        }
     }
 
     fn last_error(&mut self, value: &mut Variant) -> AddinResult {
@@ -22,6 +27,22 @@ impl Parser {
         }
     }
 
+    fn get_format(&mut self, value: &mut Variant) -> AddinResult {
+        value.set_str1c(self.format_json.as_str())?;
+        Ok(())
+    }
+
+    fn set_format(&mut self, value: &Variant) -> AddinResult {
+        let format_json = value.get_string()?;
+        self.format = if format_json.is_empty() {
+            SerializeOptions::default()
+        } else {
+            serde_json::from_str(&format_json)?
+        };
+        self.format_json = format_json;
+        Ok(())
+    }
+
     fn parse_file(
         &mut self,
         file_name: &mut Variant,
@@ -36,7 +57,7 @@ impl Parser {
         let filter = filter.get_blob()?;
         let limit = limit.get_i32()?;
 
-        let filter: Vec<filters::Filter> = serde_json::from_slice(filter)?;
+        let filter = parse_filter(filter)?;
 
         let mut buf = Vec::<u8>::new();
         let mut serializer = serde_json::Serializer::new(&mut buf);
@@ -45,13 +66,11 @@ impl Parser {
         let mut count: i32 = 0;
 
         tech_log_parser::parse_file_with_worker(file_name, &mut |event| {
-            for filter in filter.iter() {
-                if !filter.check(&event) {
-                    return Ok(true);
-                }
+            if !filter.check(&event) {
+                return Ok(true);
             }
 
-            seq.serialize_element(&event)?;
+            seq.serialize_element(&event.with_options(&self.format))?;
 
             if limit > 0 {
                 count += 1;
@@ -71,6 +90,16 @@ impl Parser {
     }
 }
 
+/// The filter is either a `Json` array of `Filter`, which must all match, or a query text,
+/// see the `query` module.
+fn parse_filter(filter: &[u8]) -> Result<Filter, Box<dyn Error>> {
+    if filter.trim_ascii_start().starts_with(b"[") {
+        Ok(Filter::And(serde_json::from_slice(filter)?))
+    } else {
+        Ok(query::parse(std::str::from_utf8(filter)?)?)
+    }
+}
+
 impl SimpleAddin for Parser {
     fn name() -> &'static CStr1C {
         name!("TechLogParser")
@@ -81,11 +110,18 @@ impl SimpleAddin for Parser {
     }
 
     fn properties() -> &'static [PropInfo<Self>] {
-        &[PropInfo {
-            name: name!("LastError"),
-            getter: Some(Self::last_error),
-            setter: None,
-        }]
+        &[
+            PropInfo {
+                name: name!("LastError"),
+                getter: Some(Self::last_error),
+                setter: None,
+            },
+            PropInfo {
+                name: name!("Format"),
+                getter: Some(Self::get_format),
+                setter: Some(Self::set_format),
+            },
+        ]
     }
 
     fn methods() -> &'static [addin1c::MethodInfo<Self>]
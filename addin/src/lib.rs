@@ -1,7 +1,6 @@
 #![allow(clippy::missing_safety_doc)]
 
 mod addin_parser;
-mod filters;
 
 use std::{
     ffi::{c_int, c_long, c_void},
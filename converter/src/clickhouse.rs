@@ -0,0 +1,231 @@
+use std::{error::Error, io::Write};
+
+use tech_log_parser::Event;
+
+use crate::{
+    Config,
+    output::{Duplicates, IntoWriter, Output},
+};
+
+const HEADER_FIELDS: [&str; 5] = ["Date", "Duration", "Name", "Level", "Props"];
+
+/// Property columns without duplicates and their names in the table.
+fn columns(config: &Config) -> Vec<(&str, String)> {
+    let mut columns = Vec::<(&str, String)>::new();
+    for name in config.columns.iter() {
+        if columns.iter().any(|(x, _)| x.eq_ignore_ascii_case(name)) {
+            continue;
+        }
+        let column = if HEADER_FIELDS.iter().any(|x| x.eq_ignore_ascii_case(name)) {
+            format!("Prop{name}")
+        } else {
+            name.clone()
+        };
+        columns.push((name, column));
+    }
+    columns
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("`{}`", name.replace('\\', "\\\\").replace('`', "\\`"))
+}
+
+/// `CREATE TABLE` statement for the columns written by `ClickhouseOutput`.
+pub fn create_table(table: &str, config: &Config) -> String {
+    let mut text = format!(
+        "CREATE TABLE IF NOT EXISTS {}\n(\n    Date DateTime64(6),\n    Duration UInt64,\n    \
+        Name LowCardinality(String),\n    Level UInt32,\n",
+        quote_identifier(table)
+    );
+    for (_, column) in columns(config) {
+        text.push_str(&format!(
+            "    {} Nullable(String),\n",
+            quote_identifier(&column)
+        ));
+    }
+    text.push_str(
+        "    Props Map(String, String)\n)\nENGINE = MergeTree\n\
+        PARTITION BY toYYYYMMDD(Date)\nORDER BY (Name, Date);\n",
+    );
+    text
+}
+
+/// Escapes a string by the rules of the `TabSeparated` format, quotes are escaped too, so the
+/// same text can be quoted inside of `Map` values.
+fn escape(out: &mut String, value: &str) {
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            '\x08' => out.push_str("\\b"),
+            '\x0c' => out.push_str("\\f"),
+            '\'' => out.push_str("\\'"),
+            c => out.push(c),
+        }
+    }
+}
+
+/// `TabSeparatedWithNames` of ClickHouse, see `create_table` for the columns: property columns
+/// are `\N` if the event has no such property and the other properties go into the `Props` map.
+/// Every file starts with the header, so files are inserted one by one. `Date` is the local time
+/// of the log without a time zone.
+pub struct ClickhouseOutput<'a, W: Write> {
+    inner: W,
+    columns: Vec<(&'a str, String)>,
+    duplicates: Duplicates,
+    line: String,
+}
+
+impl<'a, W: Write> ClickhouseOutput<'a, W> {
+    pub fn new(mut inner: W, config: &'a Config) -> std::io::Result<Self> {
+        let columns = columns(config);
+        let mut line = String::new();
+        for name in ["Date", "Duration", "Name", "Level"]
+            .into_iter()
+            .chain(columns.iter().map(|(_, column)| column.as_str()))
+            .chain(["Props"])
+        {
+            escape(&mut line, name);
+            line.push('\t');
+        }
+        line.pop();
+        line.push('\n');
+        inner.write_all(line.as_bytes())?;
+        Ok(Self {
+            inner,
+            columns,
+            duplicates: config.duplicates,
+            line,
+        })
+    }
+}
+
+impl<W: Write> Output for ClickhouseOutput<'_, W> {
+    fn write(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        let line = &mut self.line;
+        line.clear();
+        line.push_str(&event.date.format("%Y-%m-%d %H:%M:%S%.6f\t").to_string());
+        line.push_str(&format!("{}\t", event.duration.as_micros()));
+        escape(line, event.name);
+        line.push_str(&format!("\t{}\t", event.level));
+
+        for (name, _) in self.columns.iter() {
+            let mut values = event
+                .properties
+                .iter()
+                .filter(|(x, _)| x.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.str());
+            match self.duplicates {
+                Duplicates::First => match values.next() {
+                    Some(value) => escape(line, &value),
+                    None => line.push_str("\\N"),
+                },
+                Duplicates::Join => {
+                    let values: Vec<_> = values.collect();
+                    if values.is_empty() {
+                        line.push_str("\\N");
+                    } else {
+                        escape(line, &values.join("\n"));
+                    }
+                }
+            }
+            line.push('\t');
+        }
+
+        line.push('{');
+        let mut first = true;
+        for (name, value) in event.properties.iter() {
+            if self
+                .columns
+                .iter()
+                .any(|(x, _)| x.eq_ignore_ascii_case(name))
+            {
+                continue;
+            }
+            if !first {
+                line.push(',');
+            }
+            first = false;
+            line.push('\'');
+            escape(line, name);
+            line.push_str("':'");
+            escape(line, &value.str());
+            line.push('\'');
+        }
+        line.push_str("}\n");
+
+        self.inner.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(self.inner.flush()?)
+    }
+}
+
+impl<W: Write> IntoWriter<W> for ClickhouseOutput<'_, W> {
+    fn into_writer(mut self: Box<Self>) -> Result<W, Box<dyn Error>> {
+        self.finish()?;
+        Ok(self.inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+    use tech_log_parser::{Event, LogStr};
+
+    use super::{ClickhouseOutput, create_table};
+    use crate::{Config, output::Output};
+
+    #[test]
+    fn test_clickhouse() {
+        let config = Config {
+            columns: vec!["Usr".to_string(), "level".to_string(), "usr".to_string()],
+            ..Config::default()
+        };
+        assert_eq!(
+            create_table("tech_log", &config),
+            "CREATE TABLE IF NOT EXISTS `tech_log`\n(\n    Date DateTime64(6),\n    \
+            Duration UInt64,\n    Name LowCardinality(String),\n    Level UInt32,\n    \
+            `Usr` Nullable(String),\n    `Proplevel` Nullable(String),\n    \
+            Props Map(String, String)\n)\nENGINE = MergeTree\n\
+            PARTITION BY toYYYYMMDD(Date)\nORDER BY (Name, Date);\n"
+        );
+
+        let properties = [
+            ("Usr", LogStr::new(b"Admin", '\0')),
+            ("Sql", LogStr::new(b"SELECT 'a\\b',\n\t1", '\0')),
+        ];
+        let mut buf = Vec::new();
+        let mut output = ClickhouseOutput::new(&mut buf, &config).unwrap();
+        for properties in [&properties[..], &properties[1..]] {
+            output
+                .write(&Event {
+                    date: NaiveDate::from_ymd_opt(2024, 1, 4)
+                        .and_then(|date| date.and_hms_micro_opt(15, 0, 0, 5))
+                        .unwrap(),
+                    duration: Duration::from_micros(7),
+                    name: "DBMSSQL",
+                    level: 1,
+                    properties,
+                })
+                .unwrap();
+        }
+        output.finish().unwrap();
+        drop(output);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "Date\tDuration\tName\tLevel\tUsr\tProplevel\tProps\n\
+            2024-01-04 15:00:00.000005\t7\tDBMSSQL\t1\tAdmin\t\\N\t\
+            {'Sql':'SELECT \\'a\\\\b\\',\\n\\t1'}\n\
+            2024-01-04 15:00:00.000005\t7\tDBMSSQL\t1\t\\N\t\\N\t\
+            {'Sql':'SELECT \\'a\\\\b\\',\\n\\t1'}\n"
+        );
+    }
+}
@@ -0,0 +1,380 @@
+use std::{error::Error, io::Write, sync::Arc};
+
+use arrow_array::{
+    ArrayRef, Int64Array, RecordBatch, StringArray, TimestampMicrosecondArray, UInt32Array,
+    builder::{MapBuilder, StringBuilder},
+};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{Field, Schema, SchemaRef};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression, ZstdLevel},
+    file::properties::WriterProperties,
+};
+use tech_log_parser::{Event, OwnedEvent};
+
+use crate::{
+    Config,
+    output::{Duplicates, IntoWriter, Output},
+};
+
+const HEADER_FIELDS: [&str; 4] = ["Date", "Duration", "Name", "Level"];
+
+const DEFAULT_ROW_GROUP_SIZE: usize = 65_536;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColumnarFormat {
+    Parquet,
+    Arrow,
+}
+
+enum Sink<W: Write + Send> {
+    Parquet(ArrowWriter<W>),
+    Arrow(FileWriter<W>),
+}
+
+/// Writes events in batches of `Config::row_group_size` rows (65536 by default) with the columns
+/// `Date` (timestamp of the local time), `Duration` (microseconds), `Name`, `Level`, a column per
+/// property of `Config::columns` and the `Props` map with the other properties. If no columns are
+/// configured, they are inferred from the properties of the first batch.
+pub struct ColumnarOutput<'a, W: Write + Send> {
+    format: ColumnarFormat,
+    config: &'a Config,
+    inner: Option<W>,
+    sink: Option<Sink<W>>,
+    columns: Vec<String>,
+    /// Names of the `columns` in the schema, see `column_names`.
+    names: Vec<String>,
+    events: Vec<OwnedEvent>,
+}
+
+/// Names of the property columns: a property clashing with a header field or `Props` is prefixed
+/// with `Prop` until it differs from the other names. Names are compared case insensitive, as
+/// DuckDB and Spark do.
+fn column_names(columns: &[String]) -> Vec<String> {
+    let mut names = columns.to_vec();
+    for (i, column) in columns.iter().enumerate() {
+        if HEADER_FIELDS
+            .iter()
+            .chain(&["Props"])
+            .any(|x| x.eq_ignore_ascii_case(column))
+        {
+            let mut name = format!("Prop{column}");
+            while names.iter().any(|x| x.eq_ignore_ascii_case(&name)) {
+                name.insert_str(0, "Prop");
+            }
+            names[i] = name;
+        }
+    }
+    names
+}
+
+impl<'a, W: Write + Send> ColumnarOutput<'a, W> {
+    pub fn new(format: ColumnarFormat, inner: W, config: &'a Config) -> Self {
+        Self {
+            format,
+            config,
+            inner: Some(inner),
+            sink: None,
+            columns: Vec::new(),
+            names: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    fn batch_size(&self) -> usize {
+        self.config
+            .row_group_size
+            .unwrap_or(DEFAULT_ROW_GROUP_SIZE)
+            .max(1)
+    }
+
+    /// Configured or inferred property columns without duplicates.
+    fn init_columns(&mut self) {
+        let mut names: Vec<&str> = self.config.columns.iter().map(String::as_str).collect();
+        if names.is_empty() {
+            for event in self.events.iter() {
+                names.extend(event.properties.iter().map(|(name, _)| name.as_str()));
+            }
+        }
+        let mut columns = Vec::<String>::new();
+        for name in names {
+            if !columns.iter().any(|x| x.eq_ignore_ascii_case(name)) {
+                columns.push(name.to_string());
+            }
+        }
+        self.names = column_names(&columns);
+        self.columns = columns;
+    }
+
+    fn record_batch(&self) -> Result<RecordBatch, Box<dyn Error>> {
+        let events = &self.events;
+        let mut arrays: Vec<(String, ArrayRef)> = vec![
+            (
+                "Date".to_string(),
+                Arc::new(TimestampMicrosecondArray::from_iter_values(
+                    events.iter().map(|x| x.date.and_utc().timestamp_micros()),
+                )),
+            ),
+            (
+                "Duration".to_string(),
+                Arc::new(Int64Array::from_iter_values(
+                    events
+                        .iter()
+                        .map(|x| x.duration.as_micros().min(i64::MAX as u128) as i64),
+                )),
+            ),
+            (
+                "Name".to_string(),
+                Arc::new(StringArray::from_iter_values(
+                    events.iter().map(|x| x.name.as_str()),
+                )),
+            ),
+            (
+                "Level".to_string(),
+                Arc::new(UInt32Array::from_iter_values(
+                    events.iter().map(|x| x.level),
+                )),
+            ),
+        ];
+
+        for (column, column_name) in self.columns.iter().zip(self.names.iter()) {
+            let mut builder = StringBuilder::new();
+            for event in events.iter() {
+                let mut values = event
+                    .properties
+                    .iter()
+                    .filter(|(name, _)| name.eq_ignore_ascii_case(column))
+                    .map(|(_, value)| value.as_str());
+                match self.config.duplicates {
+                    Duplicates::First => builder.append_option(values.next()),
+                    Duplicates::Join => {
+                        let values: Vec<_> = values.collect();
+                        if values.is_empty() {
+                            builder.append_null();
+                        } else {
+                            builder.append_value(values.join("\n"));
+                        }
+                    }
+                }
+            }
+            arrays.push((column_name.clone(), Arc::new(builder.finish())));
+        }
+
+        let mut props = MapBuilder::new(None, StringBuilder::new(), StringBuilder::new());
+        for event in events.iter() {
+            for (name, value) in event.properties.iter() {
+                if !self.columns.iter().any(|x| x.eq_ignore_ascii_case(name)) {
+                    props.keys().append_value(name);
+                    props.values().append_value(value);
+                }
+            }
+            props.append(true)?;
+        }
+        arrays.push(("Props".to_string(), Arc::new(props.finish())));
+
+        let schema: SchemaRef = Arc::new(Schema::new(
+            arrays
+                .iter()
+                .map(|(name, array)| {
+                    let nullable = !HEADER_FIELDS.contains(&name.as_str());
+                    Field::new(name, array.data_type().clone(), nullable)
+                })
+                .collect::<Vec<_>>(),
+        ));
+        Ok(RecordBatch::try_new(
+            schema,
+            arrays.into_iter().map(|(_, array)| array).collect(),
+        )?)
+    }
+
+    fn create_sink(&mut self, schema: SchemaRef) -> Result<Sink<W>, Box<dyn Error>> {
+        let inner = self.inner.take().ok_or("the output is already finished")?;
+        Ok(match self.format {
+            ColumnarFormat::Parquet => {
+                let properties = WriterProperties::builder()
+                    .set_compression(Compression::ZSTD(ZstdLevel::default()))
+                    .set_max_row_group_size(self.batch_size())
+                    .build();
+                Sink::Parquet(ArrowWriter::try_new(inner, schema, Some(properties))?)
+            }
+            ColumnarFormat::Arrow => Sink::Arrow(FileWriter::try_new(inner, &schema)?),
+        })
+    }
+
+    fn write_batch(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.sink.is_none() {
+            self.init_columns();
+        }
+        let batch = self.record_batch()?;
+        self.events.clear();
+        let sink = match &mut self.sink {
+            Some(sink) => sink,
+            None => {
+                let sink = self.create_sink(batch.schema())?;
+                self.sink.insert(sink)
+            }
+        };
+        match sink {
+            Sink::Parquet(writer) => writer.write(&batch)?,
+            Sink::Arrow(writer) => writer.write(&batch)?,
+        }
+        Ok(())
+    }
+
+    /// Writes the rest of the events and the footer, returns the writer if it wasn't yet.
+    fn finish_sink(&mut self) -> Result<Option<W>, Box<dyn Error>> {
+        // an empty file still gets the schema of the header fields
+        if !self.events.is_empty() || self.sink.is_none() {
+            self.write_batch()?;
+        }
+        Ok(match self.sink.take() {
+            Some(Sink::Parquet(writer)) => Some(writer.into_inner()?),
+            Some(Sink::Arrow(mut writer)) => {
+                writer.finish()?;
+                Some(writer.into_inner()?)
+            }
+            None => None,
+        })
+    }
+}
+
+impl<W: Write + Send> Output for ColumnarOutput<'_, W> {
+    fn write(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        self.events.push(event.to_owned_event());
+        if self.events.len() >= self.batch_size() {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(mut inner) = self.finish_sink()? {
+            inner.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write + Send> IntoWriter<W> for ColumnarOutput<'_, W> {
+    fn into_writer(mut self: Box<Self>) -> Result<W, Box<dyn Error>> {
+        Ok(self
+            .finish_sink()?
+            .ok_or("the output is already finished")?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, time::Duration};
+
+    use arrow_array::{Array, MapArray, StringArray};
+    use arrow_ipc::reader::FileReader;
+    use chrono::NaiveDate;
+    use tech_log_parser::{Event, LogStr};
+
+    use super::{ColumnarFormat, ColumnarOutput, column_names};
+    use crate::{Config, output::Output};
+
+    fn write(format: ColumnarFormat, config: &Config) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut output = ColumnarOutput::new(format, &mut buf, config);
+        for (name, usr) in [("CALL", "Admin"), ("SDBL", ""), ("CALL", "Robot")] {
+            let properties = [
+                ("process", LogStr::new(b"rphost", '\0')),
+                ("Usr", LogStr::new(usr.as_bytes(), '\0')),
+            ];
+            output
+                .write(&Event {
+                    date: NaiveDate::from_ymd_opt(2024, 1, 4)
+                        .and_then(|date| date.and_hms_opt(15, 0, 0))
+                        .unwrap(),
+                    duration: Duration::from_micros(5),
+                    name,
+                    level: 0,
+                    properties: &properties[..if usr.is_empty() { 1 } else { 2 }],
+                })
+                .unwrap();
+        }
+        output.finish().unwrap();
+        drop(output);
+        buf
+    }
+
+    #[test]
+    fn test_arrow() {
+        let config = Config {
+            columns: vec!["usr".to_string()],
+            row_group_size: Some(2),
+            ..Config::default()
+        };
+        let buf = write(ColumnarFormat::Arrow, &config);
+        let reader = FileReader::try_new(Cursor::new(buf), None).unwrap();
+        let names: Vec<_> = reader
+            .schema()
+            .fields()
+            .iter()
+            .map(|x| x.name().clone())
+            .collect();
+        assert_eq!(names, ["Date", "Duration", "Name", "Level", "usr", "Props"]);
+
+        let batches: Vec<_> = reader.map(|x| x.unwrap()).collect();
+        assert_eq!(batches.len(), 2);
+        let usr = batches[0]
+            .column(4)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(usr.value(0), "Admin");
+        assert!(usr.is_null(1));
+        let props = batches[1]
+            .column(5)
+            .as_any()
+            .downcast_ref::<MapArray>()
+            .unwrap();
+        assert_eq!(props.value(0).len(), 1);
+    }
+
+    #[test]
+    fn test_parquet() {
+        let buf = write(ColumnarFormat::Parquet, &Config::default());
+        assert!(buf.starts_with(b"PAR1") && buf.ends_with(b"PAR1"));
+    }
+
+    #[test]
+    fn test_column_names() {
+        let config = Config {
+            columns: vec![
+                "date".to_string(),
+                "PropDate".to_string(),
+                "props".to_string(),
+            ],
+            ..Config::default()
+        };
+        assert_eq!(
+            column_names(&config.columns),
+            ["PropPropdate", "PropDate", "Propprops"]
+        );
+        let buf = write(ColumnarFormat::Arrow, &config);
+        let reader = FileReader::try_new(Cursor::new(buf), None).unwrap();
+        let names: Vec<_> = reader
+            .schema()
+            .fields()
+            .iter()
+            .map(|x| x.name().clone())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "Date",
+                "Duration",
+                "Name",
+                "Level",
+                "PropPropdate",
+                "PropDate",
+                "Propprops",
+                "Props"
+            ]
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    error::Error,
+    io::{self, BufWriter, Write},
+};
+
+use clap::ValueEnum;
+use flate2::write::GzEncoder;
+use serde::Deserialize;
+use tech_log_parser::Event;
+
+use crate::output::{IntoWriter, Output};
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, ValueEnum)]
+pub enum Compression {
+    /// `.gz`, levels 0-9, 6 by default
+    Gzip,
+    /// `.zst`, levels 1-22, 3 by default
+    Zstd,
+}
+
+impl Compression {
+    /// Suffix added to the extension of the output format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Zstd => "zst",
+        }
+    }
+
+    fn encoder<W: Write>(self, inner: W, level: Option<i32>) -> io::Result<Encoder<W>> {
+        Ok(match self {
+            Compression::Gzip => {
+                let level = match level {
+                    Some(level @ 0..=9) => flate2::Compression::new(level as u32),
+                    Some(level) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("invalid gzip compression level {level}, expected 0-9"),
+                        ));
+                    }
+                    None => flate2::Compression::default(),
+                };
+                Encoder::Gzip(GzEncoder::new(inner, level))
+            }
+            Compression::Zstd => {
+                let level = match level {
+                    Some(level @ 1..=22) => level,
+                    Some(level) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("invalid zstd compression level {level}, expected 1-22"),
+                        ));
+                    }
+                    None => zstd::DEFAULT_COMPRESSION_LEVEL,
+                };
+                Encoder::Zstd(zstd::Encoder::new(inner, level)?)
+            }
+        })
+    }
+}
+
+/// The writer given to the compressed output.
+pub enum Encoder<W: Write> {
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Writes the end of the compressed stream.
+    fn finish(self) -> io::Result<W> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            Encoder::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.flush(),
+            Encoder::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+type CompressedWriter<W> = BufWriter<Encoder<W>>;
+
+/// Wraps an output writing into the encoder, the encoder is taken back from the output and
+/// finished when the output is finished.
+pub struct CompressedOutput<'a, W: Write> {
+    output: Option<Box<dyn IntoWriter<CompressedWriter<W>> + 'a>>,
+}
+
+/// Creates the output by `create` with an encoder compressing into `inner`.
+pub fn compressed<'a, W, F>(
+    inner: W,
+    compression: Compression,
+    level: Option<i32>,
+    create: F,
+) -> Result<CompressedOutput<'a, W>, Box<dyn Error>>
+where
+    W: Write + Send + 'a,
+    F: FnOnce(Encoder<W>) -> Result<Box<dyn IntoWriter<CompressedWriter<W>> + 'a>, Box<dyn Error>>,
+{
+    let output = create(compression.encoder(inner, level)?)?;
+    Ok(CompressedOutput {
+        output: Some(output),
+    })
+}
+
+impl<W: Write> Output for CompressedOutput<'_, W> {
+    fn write(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        self.output
+            .as_mut()
+            .ok_or("the compressed output is finished")?
+            .write(event)
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(output) = self.output.take() {
+            let encoder = output
+                .into_writer()?
+                .into_inner()
+                .map_err(|e| e.into_error())?;
+            encoder.finish()?.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Read, time::Duration};
+
+    use chrono::NaiveDate;
+    use tech_log_parser::{Event, LogStr};
+
+    use super::Compression;
+    use crate::{Config, output::OutputFormat};
+
+    fn write(format: OutputFormat, config: &Config) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut output = format.create(&mut buf, config).unwrap();
+        for i in 0..1000u64 {
+            let sql = format!("SELECT {i} FROM T WHERE x = 'it''s'");
+            let properties = [
+                ("process", LogStr::new(b"rphost", '\0')),
+                ("Sql", LogStr::new(sql.as_bytes(), '\'')),
+            ];
+            output
+                .write(&Event {
+                    date: NaiveDate::from_ymd_opt(2024, 1, 4)
+                        .and_then(|date| date.and_hms_micro_opt(15, 0, 0, i as u32))
+                        .unwrap(),
+                    duration: Duration::from_micros(i),
+                    name: "DBMSSQL",
+                    level: 0,
+                    properties: &properties,
+                })
+                .unwrap();
+        }
+        output.finish().unwrap();
+        drop(output);
+        buf
+    }
+
+    #[test]
+    fn test_compressed() {
+        for format in [OutputFormat::Json, OutputFormat::Ndjson, OutputFormat::Csv] {
+            let expected = write(format, &Config::default());
+            for compression in [Compression::Gzip, Compression::Zstd] {
+                let config = Config {
+                    compression: Some(compression),
+                    compression_level: Some(1),
+                    ..Config::default()
+                };
+                let buf = write(format, &config);
+                assert!(buf.len() < expected.len());
+
+                let mut decompressed = Vec::new();
+                match compression {
+                    Compression::Gzip => flate2::read::GzDecoder::new(&buf[..])
+                        .read_to_end(&mut decompressed)
+                        .unwrap(),
+                    Compression::Zstd => zstd::Decoder::new(&buf[..])
+                        .unwrap()
+                        .read_to_end(&mut decompressed)
+                        .unwrap(),
+                };
+                assert!(decompressed == expected, "{format:?} {compression:?}");
+            }
+        }
+
+        for (compression, level) in [
+            (Compression::Gzip, 10),
+            (Compression::Zstd, 0),
+            (Compression::Zstd, -1),
+            (Compression::Zstd, 23),
+        ] {
+            let config = Config {
+                compression: Some(compression),
+                compression_level: Some(level),
+                ..Config::default()
+            };
+            assert!(OutputFormat::Json.create(Vec::new(), &config).is_err());
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use std::{
+    error::Error,
+    fs::{self, OpenOptions},
+    io,
+    path::Path,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+use clap::ValueEnum;
+
+use crate::{
+    Config, Input,
+    files::source_path,
+    manifest::{FileState, Manifest, file_head, file_version},
+    output::OutputFormat,
+    report::{FileError, Report},
+};
+
+/// What to do with an existing destination file.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Overwrite {
+    /// Convert again
+    Always,
+    /// Keep the existing file
+    Never,
+    /// Convert again if the source was modified after the destination
+    Newer,
+}
+
+impl Overwrite {
+    fn needed(self, source: &Path, dest: &Path) -> io::Result<bool> {
+        let dest = match fs::metadata(dest) {
+            Ok(dest) => dest,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(true),
+            Err(err) => return Err(err),
+        };
+        Ok(match self {
+            Overwrite::Always => true,
+            Overwrite::Never => false,
+            Overwrite::Newer => fs::metadata(source)?.modified()? > dest.modified()?,
+        })
+    }
+}
+
+/// How a source file is processed.
+enum Plan {
+    Skip,
+    Convert,
+    /// Append records starting at the offset to the destination.
+    Append(u64),
+}
+
+/// Converts the source or its records from the offset of `Plan::Append` and returns the offset
+/// after the last converted record.
+fn convert_file(
+    source: &Path,
+    dest: &Path,
+    config: &Config,
+    to: OutputFormat,
+    append: Option<u64>,
+) -> Result<u64, Box<dyn Error>> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut output = match append {
+        Some(_) => to.create(OpenOptions::new().append(true).open(dest)?, config)?,
+        None => to.open(dest, config)?,
+    };
+
+    let offset =
+        tech_log_parser::parse_file_from(source, append.unwrap_or_default(), &mut |event| {
+            config.apply(&event, |event| output.write(event))?;
+            Ok(true)
+        })?;
+
+    output.finish()?;
+    Ok(offset)
+}
+
+/// Unchanged files are skipped, new records of grown files are appended to `ndjson`, other
+/// changed files are converted again. A file is grown if its converted part is the same, see
+/// `FileState::head`.
+fn incremental_plan(
+    previous: Option<&FileState>,
+    source: &Path,
+    dest: &Path,
+    to: OutputFormat,
+    version: (u64, u64),
+) -> Plan {
+    let (size, modified) = version;
+    match previous {
+        Some(previous) if previous.dest == dest && dest.exists() => {
+            if previous.unchanged(size, modified) {
+                Plan::Skip
+            } else if to == OutputFormat::Ndjson
+                && size >= previous.offset
+                && file_head(source, previous.offset).is_ok_and(|x| x == previous.head)
+            {
+                Plan::Append(previous.offset)
+            } else {
+                Plan::Convert
+            }
+        }
+        _ => Plan::Convert,
+    }
+}
+
+/// If the source is a directory, every file is converted into the same relative path of `dest`
+/// with the extension of the output format, otherwise `dest` is the destination file.
+/// Files are converted in `jobs` threads, a failed file is reported and removed, and the others
+/// are converted anyway. With a manifest, the files are converted incrementally, see
+/// `incremental_plan`, and the manifest is updated by every run.
+pub fn run(
+    input: &Input,
+    dest: &Path,
+    to: OutputFormat,
+    overwrite: Overwrite,
+    jobs: usize,
+    manifest_path: Option<&Path>,
+    report: &Report,
+) -> Result<(), Box<dyn Error>> {
+    let config = input.config()?;
+    let files = input.files()?;
+    let manifest = match manifest_path {
+        Some(path) => Some(Mutex::new(
+            Manifest::load(path).map_err(|e| FileError::new(path, e))?,
+        )),
+        None => None,
+    };
+
+    let next = AtomicUsize::new(0);
+    let converted = AtomicUsize::new(0);
+    let appended = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
+    let convert = |relative: &Path| -> Result<(), FileError> {
+        let source = source_path(&input.source, relative);
+        let dest = if input.source.is_dir() {
+            dest.join(relative)
+                .with_extension(to.file_extension(&config))
+        } else {
+            dest.to_owned()
+        };
+        let mut version = (0, 0);
+        let plan = match &manifest {
+            Some(manifest) => {
+                version = file_version(&source).map_err(|e| FileError::new(&source, e))?;
+                let manifest = manifest.lock().unwrap();
+                incremental_plan(manifest.files.get(relative), &source, &dest, to, version)
+            }
+            None => match overwrite.needed(&source, &dest) {
+                Ok(true) => Plan::Convert,
+                Ok(false) => Plan::Skip,
+                Err(err) => return Err(FileError::new(&dest, err)),
+            },
+        };
+        let append = match plan {
+            Plan::Skip => {
+                report.verbose(format_args!("skipped: {}", dest.display()));
+                return Ok(());
+            }
+            Plan::Convert => {
+                report.verbose(format_args!("{} -> {}", source.display(), dest.display()));
+                None
+            }
+            Plan::Append(offset) => {
+                report.verbose(format_args!("{} +> {}", source.display(), dest.display()));
+                Some(offset)
+            }
+        };
+
+        let converted_file = convert_file(&source, &dest, &config, to, append).and_then(|offset| {
+            let head = match &manifest {
+                Some(_) => file_head(&source, offset)?,
+                None => 0,
+            };
+            Ok((offset, head))
+        });
+        match converted_file {
+            Ok((offset, head)) => {
+                if let Some(manifest) = &manifest {
+                    let (size, modified) = version;
+                    let state = FileState {
+                        dest,
+                        size,
+                        modified,
+                        offset,
+                        head,
+                    };
+                    manifest
+                        .lock()
+                        .unwrap()
+                        .files
+                        .insert(relative.to_owned(), state);
+                }
+                match append {
+                    Some(_) => appended.fetch_add(1, Ordering::Relaxed),
+                    None => converted.fetch_add(1, Ordering::Relaxed),
+                };
+                Ok(())
+            }
+            Err(err) => {
+                if append.is_none() {
+                    // an incomplete file would be skipped by the next run with `--overwrite newer`
+                    let _ = fs::remove_file(&dest);
+                }
+                // the file is converted from the beginning by the next run
+                if let Some(manifest) = &manifest {
+                    manifest.lock().unwrap().files.remove(relative);
+                }
+                Err(FileError::new(&source, err))
+            }
+        }
+    };
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, files.len().max(1)) {
+            scope.spawn(|| {
+                while let Some(relative) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    if let Err(err) = convert(relative) {
+                        report.error(&err);
+                        failed.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+
+    if let (Some(path), Some(manifest)) = (manifest_path, manifest) {
+        let mut manifest = manifest.into_inner().unwrap();
+        // removed logs are forgotten
+        manifest
+            .files
+            .retain(|relative, _| source_path(&input.source, relative).exists());
+        manifest.save(path).map_err(|e| FileError::new(path, e))?;
+    }
+
+    let converted = converted.into_inner();
+    let appended = appended.into_inner();
+    let failed = failed.into_inner();
+    report.info(format_args!(
+        "converted files: {converted}, appended: {appended}, skipped: {}, failed: {failed}",
+        files.len() - converted - appended - failed
+    ));
+    if failed > 0 {
+        return Err(format!("failed to convert {failed} of {} files", files.len()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use super::{Plan, incremental_plan};
+    use crate::{
+        manifest::{FileState, file_head},
+        output::OutputFormat,
+    };
+
+    /// Removes the directory even if an assertion fails.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_incremental_plan() {
+        let dir = TempDir::new("converter-plan");
+        let source = dir.0.join("24010415.log");
+        let dest = dir.0.join("24010415.ndjson");
+        fs::write(&source, "a".repeat(150)).unwrap();
+        fs::write(&dest, "").unwrap();
+        let previous = FileState {
+            dest: dest.clone(),
+            size: 100,
+            modified: 1,
+            offset: 90,
+            head: file_head(&source, 90).unwrap(),
+        };
+        let plan = |previous, dest: &Path, to, version| match incremental_plan(
+            previous, &source, dest, to, version,
+        ) {
+            Plan::Skip => "skip".to_string(),
+            Plan::Convert => "convert".to_string(),
+            Plan::Append(offset) => format!("append {offset}"),
+        };
+        let ndjson = OutputFormat::Ndjson;
+        assert_eq!(plan(None, &dest, ndjson, (100, 1)), "convert");
+        assert_eq!(plan(Some(&previous), &dest, ndjson, (100, 1)), "skip");
+        assert_eq!(plan(Some(&previous), &dest, ndjson, (150, 2)), "append 90");
+        assert_eq!(plan(Some(&previous), &dest, ndjson, (50, 2)), "convert");
+        assert_eq!(
+            plan(Some(&previous), &dest, OutputFormat::Json, (150, 2)),
+            "convert"
+        );
+        let other = PathBuf::from("other.ndjson");
+        assert_eq!(plan(Some(&previous), &other, ndjson, (100, 1)), "convert");
+
+        // the log was rotated and the new one is already larger than the converted part
+        fs::write(&source, "b".repeat(150)).unwrap();
+        assert_eq!(plan(Some(&previous), &dest, ndjson, (150, 2)), "convert");
+
+        fs::remove_file(&dest).unwrap();
+        assert_eq!(plan(Some(&previous), &dest, ndjson, (100, 1)), "convert");
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+/// Which files of a source directory are processed: tech log files (`yymmddhh.log`) matching
+/// the include globs, if any, and none of the exclude globs. Globs are matched against the path
+/// relative to the source directory, e.g. `rphost_*/**` or `**/24010415.log`.
+pub struct Selection {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+fn glob_set(globs: &[String]) -> Result<Option<GlobSet>, globset::Error> {
+    if globs.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob)?);
+    }
+    Ok(Some(builder.build()?))
+}
+
+impl Selection {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, globset::Error> {
+        Ok(Self {
+            include: glob_set(include)?,
+            exclude: glob_set(exclude)?,
+        })
+    }
+
+    fn matches(&self, relative: &Path) -> bool {
+        file_name_valid(relative)
+            && self.include.as_ref().is_none_or(|x| x.is_match(relative))
+            && !self.exclude.as_ref().is_some_and(|x| x.is_match(relative))
+    }
+}
+
+fn file_name_valid(name: impl AsRef<Path>) -> bool {
+    name.as_ref()
+        .extension()
+        .map(|ext| ext == "log")
+        .unwrap_or_default()
+        && name
+            .as_ref()
+            .file_stem()
+            .and_then(|x| x.to_str())
+            .map(|x| {
+                if x.len() < 8 {
+                    return false;
+                }
+                let Some((_, x)) = x.split_at_checked(x.len() - 8) else {
+                    return false;
+                };
+                x.chars().all(char::is_numeric)
+            })
+            .unwrap_or_default()
+}
+
+fn collect_dir(
+    dir: &Path,
+    relative: &Path,
+    selection: &Selection,
+    files: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let relative = relative.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            collect_dir(&entry.path(), &relative, selection, files)?;
+        } else if selection.matches(&relative) {
+            files.push(relative);
+        }
+    }
+    Ok(())
+}
+
+/// Selected files of `source` as paths relative to it, sorted. If `source` is a file, the result
+/// is its file name (if it is selected) and the full path is `source` itself, see `source_path`.
+pub fn collect_files(source: &Path, selection: &Selection) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if fs::metadata(source)?.is_dir() {
+        collect_dir(source, Path::new(""), selection, &mut files)?;
+    } else if let Some(name) = source.file_name()
+        && selection.matches(Path::new(name))
+    {
+        files.push(PathBuf::from(name));
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Full path of a file returned by `collect_files`.
+pub fn source_path(source: &Path, relative: &Path) -> PathBuf {
+    if source.is_dir() {
+        source.join(relative)
+    } else {
+        source.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::Selection;
+
+    #[test]
+    fn test_selection() {
+        let selection =
+            Selection::new(&["rphost_*/**".to_string()], &["**/2401*".to_string()]).unwrap();
+        assert!(selection.matches(Path::new("rphost_123/23122609.log")));
+        assert!(!selection.matches(Path::new("rmngr_123/23122609.log")));
+        assert!(!selection.matches(Path::new("rphost_123/24010415.log")));
+        assert!(!selection.matches(Path::new("rphost_123/readme.txt")));
+
+        let all = Selection::new(&[], &[]).unwrap();
+        assert!(all.matches(Path::new("23122609.log")));
+        assert!(!all.matches(Path::new("2312260.log")));
+    }
+}
@@ -0,0 +1,34 @@
+use std::error::Error;
+
+use tech_log_parser::query;
+
+use crate::{
+    Input,
+    files::source_path,
+    output::OutputFormat,
+    report::{FileError, Report},
+};
+
+pub fn run(query: &str, input: &Input, report: &Report) -> Result<(), Box<dyn Error>> {
+    let mut config = input.config()?;
+    config.add_filter(query::parse(query)?);
+
+    let mut output = OutputFormat::Ndjson.create(std::io::stdout(), &config)?;
+    let mut count: u64 = 0;
+    for relative in input.files()?.iter() {
+        let source = source_path(&input.source, relative);
+        report.verbose(source.display());
+        tech_log_parser::parse_file(&source, &mut |event| {
+            config.apply(&event, |event| {
+                count += 1;
+                output.write(event)
+            })?;
+            Ok(true)
+        })
+        .map_err(|e| FileError::new(&source, e))?;
+    }
+    output.finish()?;
+
+    report.info(format_args!("found events: {count}"));
+    Ok(())
+}
@@ -1,95 +1,348 @@
+mod clickhouse;
+#[cfg(feature = "parquet")]
+mod columnar;
+mod compress;
+mod convert;
+mod files;
+mod grep;
+mod manifest;
+mod merge;
+mod output;
+mod report;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod stats;
+
 use std::{
-    fs::{self, File},
-    io::{BufWriter, Write},
-    path::Path,
+    error::Error,
+    fs::File,
+    path::{Path, PathBuf},
+    process::ExitCode,
     time::Instant,
 };
 
-use serde::{Serializer, ser::SerializeSeq};
+use chrono::NaiveDateTime;
+use clap::{Args, Parser, Subcommand};
+use serde::Deserialize;
+use tech_log_parser::{
+    Event, SerializeOptions, aggregate::GroupKey, filters::Filter, query, redact::Redactor,
+};
 
-fn convert_file(
-    source: impl AsRef<Path>,
-    dest: impl AsRef<Path>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    fs::create_dir_all(dest.as_ref().parent().ok_or("Invalid destination")?)?;
+use crate::{
+    compress::Compression,
+    convert::Overwrite,
+    files::Selection,
+    merge::Window,
+    output::{Duplicates, OutputFormat},
+    report::{ErrorFormat, FileError, Report},
+};
 
-    let file = File::create(dest)?;
-    let mut buf = BufWriter::new(file);
-    let mut serializer = serde_json::Serializer::new(&mut buf);
-    let mut seq = serializer.serialize_seq(None)?;
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+struct Config {
+    format: SerializeOptions,
+    redact: Option<Redactor>,
+    filter: Option<Filter>,
+    /// Properties written as columns by the tabular formats.
+    columns: Vec<String>,
+    duplicates: Duplicates,
+    /// Rows in a row group of the columnar formats.
+    row_group_size: Option<usize>,
+    compression: Option<Compression>,
+    compression_level: Option<i32>,
+}
 
-    tech_log_parser::parse_file(source, &mut |event| {
-        seq.serialize_element(&event)?;
-        Ok(true)
-    })?;
+impl Config {
+    fn load(file_name: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(file_name)?;
+        Ok(serde_json::from_reader(file)?)
+    }
 
-    seq.end()?;
-    buf.flush()?;
-    Ok(())
+    /// Adds the filter to the configured one, both must match.
+    fn add_filter(&mut self, filter: Filter) {
+        self.filter = Some(match self.filter.take() {
+            Some(other) => Filter::And(vec![other, filter]),
+            None => filter,
+        });
+    }
+
+    /// Calls `f` for the event if it passes the filter, redacted if configured.
+    fn apply<F>(&self, event: &Event, f: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnOnce(&Event) -> Result<(), Box<dyn Error>>,
+    {
+        if self.filter.as_ref().is_some_and(|x| !x.check(event)) {
+            return Ok(());
+        }
+        match &self.redact {
+            Some(redactor) => redactor.redact(event).with_event(f),
+            None => f(event),
+        }
+    }
 }
 
-fn file_name_valid(name: impl AsRef<Path>) -> bool {
-    name.as_ref()
-        .extension()
-        .map(|ext| ext == "log")
-        .unwrap_or_default()
-        && name
-            .as_ref()
-            .file_stem()
-            .and_then(|x| x.to_str())
-            .map(|x| {
-                if x.len() < 8 {
-                    return false;
-                }
-                let Some((_, x)) = x.split_at_checked(x.len() - 8) else {
-                    return false;
-                };
-                x.chars().all(char::is_numeric)
-            })
-            .unwrap_or_default()
+/// Source files and how their events are selected.
+#[derive(Args)]
+struct Input {
+    /// Tech log file or directory
+    source: PathBuf,
+    /// Settings file in JSON with the `Format`, `Redact` and `Filter` fields
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    /// Filter query, e.g. `name = DBMSSQL and duration > 1s`, combined with `Filter` of the settings
+    #[arg(short, long)]
+    filter: Option<String>,
+    /// Process only files matching the glob, relative to the source directory, e.g. `rphost_*/**`
+    #[arg(long)]
+    include: Vec<String>,
+    /// Skip files matching the glob, relative to the source directory
+    #[arg(long)]
+    exclude: Vec<String>,
+    /// Properties written as columns by the tabular formats, e.g. `Usr,Sql,Context`,
+    /// replace `Columns` of the settings
+    #[arg(long, value_delimiter = ',')]
+    columns: Vec<String>,
+    /// Which value of a property that occurs several times in an event goes into its column
+    #[arg(long, value_enum)]
+    duplicates: Option<Duplicates>,
+    /// Rows in a row group of the columnar formats, 65536 by default
+    #[arg(long)]
+    row_group_size: Option<usize>,
+    /// Compress the output, the extension of converted files gets `.gz` or `.zst`
+    #[arg(long, value_enum)]
+    compression: Option<Compression>,
+    /// Compression level, see `--compression`
+    #[arg(long)]
+    compression_level: Option<i32>,
 }
 
-fn process_dir(
-    source: impl AsRef<Path>,
-    dest: impl AsRef<Path>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    if source.as_ref().is_dir() {
-        for entry in fs::read_dir(source)? {
-            let entry = entry?;
-
-            if entry.path().is_dir() {
-                let dest = dest.as_ref().join(entry.file_name());
-                process_dir(entry.path(), dest)?;
-            } else if let Some(name) = entry.path().file_stem().and_then(|x| x.to_str()) {
-                let dest = dest.as_ref().join(format!("{name}.json"));
-                process_dir(entry.path(), dest)?;
-            }
+impl Input {
+    fn config(&self) -> Result<Config, Box<dyn Error>> {
+        let mut config = match &self.config {
+            Some(file_name) => Config::load(file_name).map_err(|e| FileError::new(file_name, e))?,
+            None => Config::default(),
+        };
+        if let Some(text) = &self.filter {
+            config.add_filter(query::parse(text)?);
+        }
+        if !self.columns.is_empty() {
+            config.columns = self.columns.clone();
+        }
+        if let Some(duplicates) = self.duplicates {
+            config.duplicates = duplicates;
         }
-    } else if file_name_valid(&source) {
-        let dest = dest
-            .as_ref()
-            .to_str()
-            .ok_or("Invalid destination")?
-            .replace(".log", ".json");
-        convert_file(source, dest)?;
+        if self.row_group_size.is_some() {
+            config.row_group_size = self.row_group_size;
+        }
+        if self.compression.is_some() {
+            config.compression = self.compression;
+        }
+        if self.compression_level.is_some() {
+            config.compression_level = self.compression_level;
+        }
+        Ok(config)
+    }
+
+    /// Selected files relative to the source, see `files::collect_files`.
+    fn files(&self) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let selection = Selection::new(&self.include, &self.exclude)?;
+        files::collect_files(&self.source, &selection)
+            .map_err(|e| FileError::new(&self.source, e).into())
     }
+}
 
-    Ok(())
+/// Converts and analyzes 1C:Enterprise tech logs.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// Print errors only
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Print every processed file
+    #[arg(short, long, global = true)]
+    verbose: bool,
+    /// Format of errors printed to stderr
+    #[arg(long, global = true, value_enum, default_value_t = ErrorFormat::Text)]
+    error_format: ErrorFormat,
+    #[command(subcommand)]
+    command: Command,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut args = std::env::args();
-    let Some(source) = &args.nth(1) else {
-        println!("usage: converter /path/to/log/dir/source /path/to/log/dir/destination");
-        return Ok(());
+#[derive(Subcommand)]
+enum Command {
+    /// Convert a file or a directory, keeping the layout of subdirectories
+    Convert {
+        #[command(flatten)]
+        input: Input,
+        /// Destination file or directory
+        dest: PathBuf,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Json)]
+        to: OutputFormat,
+        /// What to do with existing destination files
+        #[arg(long, value_enum, default_value_t = Overwrite::Always)]
+        overwrite: Overwrite,
+        /// Number of files converted in parallel
+        #[arg(short, long, default_value_t = 1)]
+        jobs: usize,
+        /// Convert incrementally, keeping the state of converted files in this JSON file: unchanged
+        /// files are skipped and new records of growing files are appended to `ndjson`
+        #[arg(long, conflicts_with = "overwrite")]
+        manifest: Option<PathBuf>,
+    },
+    /// Print events matching a query to stdout in NDJSON, one JSON object per line
+    Grep {
+        /// Filter query, e.g. `Usr = Admin and Descr contains deadlock`
+        query: String,
+        #[command(flatten)]
+        input: Input,
+    },
+    /// Print duration statistics of events grouped by keys
+    Stats {
+        #[command(flatten)]
+        input: Input,
+        /// Group key: `name`, `level`, `sql` (SQL fingerprint), `sql-text`, `context-top`,
+        /// `context-entry` or a property name; can be repeated
+        #[arg(short, long, value_parser = parse_group_key, default_value = "name")]
+        group_by: Vec<GroupKey>,
+        /// Print only the first groups by the total duration
+        #[arg(long)]
+        top: Option<usize>,
+        /// Print a JSON array instead of a table
+        #[arg(long)]
+        json: bool,
+        /// Number of files processed in parallel
+        #[arg(short, long, default_value_t = 1)]
+        jobs: usize,
+    },
+    /// Merge all events of the source into one file sorted by date
+    Merge {
+        #[command(flatten)]
+        input: Input,
+        /// Destination file
+        dest: PathBuf,
+        /// Output format, any but `log`
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Json)]
+        to: OutputFormat,
+        /// Merge events since the date, e.g. `2024-01-04T15:30:00`
+        #[arg(long, value_parser = parse_date)]
+        since: Option<NaiveDateTime>,
+        /// Merge events before the date
+        #[arg(long, value_parser = parse_date)]
+        until: Option<NaiveDateTime>,
+        /// Property with the source directory of an event, e.g. `rphost_1234`, none if empty
+        #[arg(long, default_value = "Source")]
+        source_prop: String,
+    },
+    /// Print the ClickHouse `CREATE TABLE` statement for the `clickhouse` output format
+    Ddl {
+        /// Table name
+        #[arg(long, default_value = "tech_log")]
+        table: String,
+        /// Settings file in JSON, only `Columns` is used
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+        /// Properties written as columns, replace `Columns` of the settings
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<String>,
+    },
+}
+
+fn parse_date(value: &str) -> Result<NaiveDateTime, String> {
+    query::parse_date(value).ok_or_else(|| format!("invalid date `{value}`"))
+}
+
+fn parse_group_key(value: &str) -> Result<GroupKey, String> {
+    let key = match value.to_ascii_lowercase().as_str() {
+        "name" => GroupKey::Name,
+        "level" => GroupKey::Level,
+        "sql" => GroupKey::SqlFingerprint,
+        "sql-text" => GroupKey::SqlText,
+        "context-top" => GroupKey::ContextTop,
+        "context-entry" => GroupKey::ContextEntry,
+        "" => return Err("empty group key".to_string()),
+        _ => GroupKey::Prop(value.to_string()),
     };
-    let Some(dest) = &args.next() else {
-        println!("usage: converter /path/to/log/dir/source /path/to/log/dir/destination");
-        return Ok(());
+    Ok(key)
+}
+
+fn run(command: &Command, report: &Report) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Convert {
+            input,
+            dest,
+            to,
+            overwrite,
+            jobs,
+            manifest,
+        } => convert::run(
+            input,
+            dest,
+            *to,
+            *overwrite,
+            *jobs,
+            manifest.as_deref(),
+            report,
+        ),
+        Command::Grep { query, input } => grep::run(query, input, report),
+        Command::Stats {
+            input,
+            group_by,
+            top,
+            json,
+            jobs,
+        } => stats::run(input, group_by, *top, *json, *jobs, report),
+        Command::Merge {
+            input,
+            dest,
+            to,
+            since,
+            until,
+            source_prop,
+        } => {
+            let window = Window {
+                since: *since,
+                until: *until,
+            };
+            merge::run(input, dest, *to, window, source_prop, report)
+        }
+        Command::Ddl {
+            table,
+            config,
+            columns,
+        } => {
+            let mut config = match config {
+                Some(file_name) => {
+                    Config::load(file_name).map_err(|e| FileError::new(file_name, e))?
+                }
+                None => Config::default(),
+            };
+            if !columns.is_empty() {
+                config.columns = columns.clone();
+            }
+            print!("{}", clickhouse::create_table(table, &config));
+            Ok(())
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let report = Report {
+        quiet: cli.quiet,
+        verbose: cli.verbose,
+        error_format: cli.error_format,
     };
 
     let start = Instant::now();
-    process_dir(source, dest)?;
-    println!("duration: {:?}", start.elapsed());
-    Ok(())
+    match run(&cli.command, &report) {
+        Ok(()) => {
+            report.info(format_args!("duration: {:?}", start.elapsed()));
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            report.error(err.as_ref());
+            ExitCode::FAILURE
+        }
+    }
 }
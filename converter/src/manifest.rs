@@ -0,0 +1,87 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// What was converted from a source file by the previous runs.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FileState {
+    pub dest: PathBuf,
+    pub size: u64,
+    /// Modification time in microseconds since the Unix epoch.
+    pub modified: u64,
+    /// End of the last converted record, new records of a growing file are read from here.
+    pub offset: u64,
+    /// `file_head` of the source at `offset`, a rotated or rewritten file has another one.
+    #[serde(default)]
+    pub head: u32,
+}
+
+impl FileState {
+    /// Whether the source has the same size and modification time.
+    pub fn unchanged(&self, size: u64, modified: u64) -> bool {
+        self.size == size && self.modified == modified
+    }
+}
+
+/// Size and modification time of a file, see `FileState`.
+pub fn file_version(path: &Path) -> io::Result<(u64, u64)> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64;
+    Ok((metadata.len(), modified))
+}
+
+/// Number of bytes at the beginning of a file hashed by `file_head`.
+const HEAD_LEN: u64 = 64 * 1024;
+
+/// CRC32 of the bytes of a file before `offset`, but not more than the first 64 KiB.
+pub fn file_head(path: &Path, offset: u64) -> io::Result<u32> {
+    let mut buf = Vec::new();
+    File::open(path)?
+        .take(offset.min(HEAD_LEN))
+        .read_to_end(&mut buf)?;
+    let mut crc = flate2::Crc::new();
+    crc.update(&buf);
+    Ok(crc.sum())
+}
+
+/// States of converted files by their paths relative to the source.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Manifest {
+    pub files: BTreeMap<PathBuf, FileState>,
+}
+
+impl Manifest {
+    /// An empty manifest if the file doesn't exist yet.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        match File::open(path) {
+            Ok(file) => Ok(serde_json::from_reader(io::BufReader::new(file))?),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Writes a temporary file and renames it, so an interrupted run doesn't spoil the manifest.
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&temp, path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap},
+    error::Error,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use chrono::{NaiveDateTime, TimeDelta};
+use tech_log_parser::{EventReader, OwnedEvent};
+
+use crate::{
+    Input,
+    files::source_path,
+    output::OutputFormat,
+    report::{FileError, Report},
+};
+
+/// Time window of merged events, `until` is excluded.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Window {
+    pub since: Option<NaiveDateTime>,
+    pub until: Option<NaiveDateTime>,
+}
+
+impl Window {
+    fn contains(&self, date: NaiveDateTime) -> bool {
+        self.since.is_none_or(|since| date >= since) && self.until.is_none_or(|until| date < until)
+    }
+
+    /// Whether any event of the hour starting at `hour` can be in the window.
+    fn overlaps_hour(&self, hour: NaiveDateTime) -> bool {
+        self.since
+            .is_none_or(|since| hour + TimeDelta::hours(1) > since)
+            && self.until.is_none_or(|until| hour < until)
+    }
+}
+
+/// Directory of a log file relative to the source, e.g. `rphost_1234`, `.` for files in the
+/// source itself.
+fn source_dir(relative: &Path) -> String {
+    match relative.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent
+            .iter()
+            .map(|x| x.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        _ => ".".to_string(),
+    }
+}
+
+/// A file of the merged hour with its next event in the window.
+struct Stream {
+    source: PathBuf,
+    dir: String,
+    reader: EventReader<File>,
+    event: OwnedEvent,
+}
+
+/// Reads the next event in the window, events of a file go by date, so the file ends at
+/// the first event after the window.
+fn next_in_window(
+    reader: &mut EventReader<File>,
+    window: Window,
+) -> Result<Option<OwnedEvent>, Box<dyn Error>> {
+    while let Some(event) = reader.next_event()? {
+        if window.until.is_some_and(|until| event.date >= until) {
+            break;
+        }
+        if window.contains(event.date) {
+            return Ok(Some(event));
+        }
+    }
+    Ok(None)
+}
+
+/// Files of one hour are merged by the date of their next event, so only one event of every
+/// file is kept in memory. Hours outside of the window are skipped without reading and a file
+/// isn't read after the end of the window. If `source_prop` isn't empty, every event gets
+/// the property with its source directory, see `source_dir`.
+pub fn run(
+    input: &Input,
+    dest: &Path,
+    to: OutputFormat,
+    window: Window,
+    source_prop: &str,
+    report: &Report,
+) -> Result<(), Box<dyn Error>> {
+    if to == OutputFormat::Log {
+        // a log file holds one hour only
+        return Err("merge doesn't support the log format".into());
+    }
+    let config = input.config()?;
+
+    let mut hours = BTreeMap::<_, Vec<PathBuf>>::new();
+    for relative in input.files()? {
+        let name = relative
+            .file_name()
+            .map(|x| x.to_owned())
+            .unwrap_or_default();
+        if tech_log_parser::parse_date_file(&name).is_some_and(|x| !window.overlaps_hour(x)) {
+            continue;
+        }
+        hours.entry(name).or_default().push(relative);
+    }
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut output = to
+        .open(dest, &config)
+        .map_err(|e| FileError::new(dest, e))?;
+
+    let mut count = 0;
+    for files in hours.values() {
+        let mut streams = Vec::with_capacity(files.len());
+        let mut heap = BinaryHeap::with_capacity(files.len());
+        for relative in files {
+            let source = source_path(&input.source, relative);
+            report.verbose(source.display());
+            let mut reader = EventReader::open(&source).map_err(|e| FileError::new(&source, e))?;
+            if let Some(event) =
+                next_in_window(&mut reader, window).map_err(|e| FileError::new(&source, e))?
+            {
+                heap.push(Reverse((event.date, streams.len())));
+                streams.push(Stream {
+                    source,
+                    dir: source_dir(relative),
+                    reader,
+                    event,
+                });
+            }
+        }
+
+        while let Some(Reverse((_, i))) = heap.pop() {
+            let stream = &mut streams[i];
+            stream.event.with_event(|event| {
+                config.apply(event, |event| {
+                    count += 1;
+                    if source_prop.is_empty() {
+                        return output.write(event);
+                    }
+                    let mut event = event.to_owned_event();
+                    event
+                        .properties
+                        .push((source_prop.to_string(), stream.dir.clone()));
+                    event.with_event(|event| output.write(event))
+                })
+            })?;
+            if let Some(event) = next_in_window(&mut stream.reader, window)
+                .map_err(|e| FileError::new(&stream.source, e))?
+            {
+                heap.push(Reverse((event.date, i)));
+                stream.event = event;
+            }
+        }
+    }
+    output.finish()?;
+
+    report.info(format_args!("merged events: {count}"));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use chrono::NaiveDate;
+
+    use super::{Window, run, source_dir};
+    use crate::{
+        Input,
+        output::OutputFormat,
+        report::{ErrorFormat, Report},
+    };
+
+    /// Removes the directory even if an assertion fails.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_window() {
+        let date = |hour, min| {
+            NaiveDate::from_ymd_opt(2024, 1, 4)
+                .and_then(|date| date.and_hms_opt(hour, min, 0))
+                .unwrap()
+        };
+        let window = Window {
+            since: Some(date(15, 30)),
+            until: Some(date(17, 0)),
+        };
+        assert!(!window.overlaps_hour(date(14, 0)));
+        assert!(window.overlaps_hour(date(15, 0)));
+        assert!(window.overlaps_hour(date(16, 0)));
+        assert!(!window.overlaps_hour(date(17, 0)));
+        assert!(!window.contains(date(15, 29)));
+        assert!(window.contains(date(15, 30)));
+        assert!(!window.contains(date(17, 0)));
+        assert!(Window::default().contains(date(0, 0)));
+
+        assert_eq!(
+            source_dir(Path::new("srv/rphost_12/24010415.log")),
+            "srv/rphost_12"
+        );
+        assert_eq!(source_dir(Path::new("24010415.log")), ".");
+    }
+
+    #[test]
+    fn test_run() {
+        let dir = TempDir::new("converter-merge");
+        let source = dir.0.join("logs");
+        fs::create_dir_all(source.join("rphost_1")).unwrap();
+        let log = |records: &[(u32, u32, &str)]| {
+            records
+                .iter()
+                .map(|(min, sec, name)| {
+                    format!("{min:02}:{sec:02}.000000-1,{name},0,process=x\r\n")
+                })
+                .collect::<String>()
+        };
+        fs::write(
+            source.join("rphost_1/24010415.log"),
+            log(&[(10, 0, "A1"), (20, 0, "A2"), (20, 0, "A3"), (40, 0, "A4")]),
+        )
+        .unwrap();
+        fs::write(
+            source.join("24010415.log"),
+            log(&[(5, 0, "B1"), (20, 30, "B2"), (30, 0, "B3"), (50, 0, "B4")]),
+        )
+        .unwrap();
+
+        let input = Input {
+            source,
+            config: None,
+            filter: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            columns: Vec::new(),
+            duplicates: None,
+            row_group_size: None,
+            compression: None,
+            compression_level: None,
+        };
+        let report = Report {
+            quiet: true,
+            verbose: false,
+            error_format: ErrorFormat::Text,
+        };
+        let date = |min| {
+            NaiveDate::from_ymd_opt(2024, 1, 4)
+                .and_then(|date| date.and_hms_opt(15, min, 0))
+                .unwrap()
+        };
+        let window = Window {
+            since: Some(date(10)),
+            until: Some(date(45)),
+        };
+        let dest = dir.0.join("merged.ndjson");
+        run(
+            &input,
+            &dest,
+            OutputFormat::Ndjson,
+            window,
+            "Source",
+            &report,
+        )
+        .unwrap();
+
+        let events: Vec<(String, String)> = fs::read_to_string(&dest)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let value: serde_json::Value = serde_json::from_str(line).unwrap();
+                (
+                    value["Name"].as_str().unwrap().to_string(),
+                    value["Props"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .find(|prop| prop[0] == "Source")
+                        .and_then(|prop| prop[1].as_str())
+                        .unwrap()
+                        .to_string(),
+                )
+            })
+            .collect();
+        let names: Vec<_> = events.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["A1", "A2", "A3", "B2", "B3", "A4"]);
+        assert_eq!(events[0].1, "rphost_1");
+        assert_eq!(events[3].1, ".");
+    }
+}
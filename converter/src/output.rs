@@ -0,0 +1,367 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use tech_log_parser::{Event, LogWriter, SerializeOptions};
+
+#[cfg(feature = "parquet")]
+use crate::columnar::{ColumnarFormat, ColumnarOutput};
+#[cfg(feature = "sqlite")]
+use crate::sqlite::SqliteOutput;
+use crate::{Config, clickhouse::ClickhouseOutput, compress};
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// A JSON array of events.
+    Json,
+    /// One JSON object per line, can be streamed and appended.
+    Ndjson,
+    /// The tech log format itself, e.g. for filtered or redacted copies of logs.
+    Log,
+    /// Comma separated values: `Date`, `Duration`, `Name`, `Level` and the property columns.
+    Csv,
+    /// Same as `csv`, separated by tabs.
+    Tsv,
+    /// `TabSeparatedWithNames` of ClickHouse for the table printed by the `ddl` command.
+    Clickhouse,
+    /// Apache Parquet with typed header columns, property columns and a map of other properties.
+    #[cfg(feature = "parquet")]
+    Parquet,
+    /// Arrow IPC file with the same columns as `parquet`.
+    #[cfg(feature = "parquet")]
+    Arrow,
+    /// SQLite database with the `Events` table, the property columns and the `Props` table.
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+/// Which value of a property that occurs several times in an event goes into its column.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, ValueEnum)]
+pub enum Duplicates {
+    /// The first value
+    #[default]
+    First,
+    /// All values separated by line breaks
+    Join,
+}
+
+impl OutputFormat {
+    /// Extension of converted files.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Log => "log",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Tsv | OutputFormat::Clickhouse => "tsv",
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => "parquet",
+            #[cfg(feature = "parquet")]
+            OutputFormat::Arrow => "arrow",
+            #[cfg(feature = "sqlite")]
+            OutputFormat::Sqlite => "sqlite",
+        }
+    }
+
+    /// Extension of converted files with the suffix of the configured compression.
+    pub fn file_extension(self, config: &Config) -> String {
+        match config.compression {
+            Some(compression) => format!("{}.{}", self.extension(), compression.extension()),
+            None => self.extension().to_string(),
+        }
+    }
+
+    /// Creates the output file, formats that can't be streamed are supported only here.
+    pub fn open<'a>(
+        self,
+        path: &Path,
+        config: &'a Config,
+    ) -> Result<Box<dyn Output + 'a>, Box<dyn Error>> {
+        match self {
+            #[cfg(feature = "sqlite")]
+            OutputFormat::Sqlite => {
+                if config.compression.is_some() {
+                    return Err("the sqlite format can't be compressed".into());
+                }
+                Ok(Box::new(SqliteOutput::create(path, config)?))
+            }
+            _ => self.create(File::create(path)?, config),
+        }
+    }
+
+    /// Creates the output writing into `inner`, compressed if configured.
+    pub fn create<'a, W: Write + Send + 'a>(
+        self,
+        inner: W,
+        config: &'a Config,
+    ) -> Result<Box<dyn Output + 'a>, Box<dyn Error>> {
+        match config.compression {
+            Some(compression) => Ok(Box::new(compress::compressed(
+                inner,
+                compression,
+                config.compression_level,
+                |encoder| self.create_uncompressed(encoder, config),
+            )?)),
+            None => Ok(self.create_uncompressed(inner, config)?),
+        }
+    }
+
+    fn create_uncompressed<'a, W: Write + Send + 'a>(
+        self,
+        inner: W,
+        config: &'a Config,
+    ) -> Result<Box<dyn IntoWriter<BufWriter<W>> + 'a>, Box<dyn Error>> {
+        let inner = BufWriter::new(inner);
+        let format = &config.format;
+        Ok(match self {
+            OutputFormat::Json => Box::new(JsonOutput::new(inner, format)?),
+            OutputFormat::Ndjson => Box::new(NdjsonOutput { inner, format }),
+            OutputFormat::Csv => Box::new(CsvOutput::new(inner, b',', config)?),
+            OutputFormat::Tsv => Box::new(CsvOutput::new(inner, b'\t', config)?),
+            OutputFormat::Clickhouse => Box::new(ClickhouseOutput::new(inner, config)?),
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => {
+                Box::new(ColumnarOutput::new(ColumnarFormat::Parquet, inner, config))
+            }
+            #[cfg(feature = "parquet")]
+            OutputFormat::Arrow => {
+                Box::new(ColumnarOutput::new(ColumnarFormat::Arrow, inner, config))
+            }
+            #[cfg(feature = "sqlite")]
+            OutputFormat::Sqlite => return Err("the sqlite format is written to files only".into()),
+            OutputFormat::Log => {
+                let mut writer = LogWriter::new(inner);
+                writer.write_bom()?;
+                Box::new(writer)
+            }
+        })
+    }
+}
+
+/// Destination of converted events.
+pub trait Output {
+    fn write(&mut self, event: &Event) -> Result<(), Box<dyn Error>>;
+
+    /// Writes the end of the output and flushes it.
+    fn finish(&mut self) -> Result<(), Box<dyn Error>>;
+}
+
+/// An output that gives its writer back, e.g. to finish the compressed stream it wrote into.
+pub trait IntoWriter<W>: Output {
+    /// Writes the end of the output and returns the writer.
+    fn into_writer(self: Box<Self>) -> Result<W, Box<dyn Error>>;
+}
+
+struct JsonOutput<'a, W: Write> {
+    inner: W,
+    format: &'a SerializeOptions,
+    first: bool,
+}
+
+impl<'a, W: Write> JsonOutput<'a, W> {
+    fn new(mut inner: W, format: &'a SerializeOptions) -> std::io::Result<Self> {
+        inner.write_all(b"[")?;
+        Ok(Self {
+            inner,
+            format,
+            first: true,
+        })
+    }
+}
+
+impl<W: Write> Output for JsonOutput<'_, W> {
+    fn write(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        if !self.first {
+            self.inner.write_all(b",")?;
+        }
+        self.first = false;
+        serde_json::to_writer(&mut self.inner, &event.with_options(self.format))?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.inner.write_all(b"]")?;
+        self.inner.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write> IntoWriter<W> for JsonOutput<'_, W> {
+    fn into_writer(mut self: Box<Self>) -> Result<W, Box<dyn Error>> {
+        self.finish()?;
+        Ok(self.inner)
+    }
+}
+
+struct NdjsonOutput<'a, W: Write> {
+    inner: W,
+    format: &'a SerializeOptions,
+}
+
+impl<W: Write> Output for NdjsonOutput<'_, W> {
+    fn write(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer(&mut self.inner, &event.with_options(self.format))?;
+        self.inner.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(self.inner.flush()?)
+    }
+}
+
+impl<W: Write> IntoWriter<W> for NdjsonOutput<'_, W> {
+    fn into_writer(mut self: Box<Self>) -> Result<W, Box<dyn Error>> {
+        self.finish()?;
+        Ok(self.inner)
+    }
+}
+
+struct CsvOutput<'a, W: Write> {
+    writer: csv::Writer<W>,
+    config: &'a Config,
+}
+
+impl<'a, W: Write> CsvOutput<'a, W> {
+    fn new(inner: W, delimiter: u8, config: &'a Config) -> csv::Result<Self> {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(delimiter)
+            .terminator(csv::Terminator::CRLF)
+            .from_writer(inner);
+        writer.write_record(
+            ["Date", "Duration", "Name", "Level"]
+                .iter()
+                .copied()
+                .chain(config.columns.iter().map(String::as_str)),
+        )?;
+        Ok(Self { writer, config })
+    }
+}
+
+impl<W: Write> Output for CsvOutput<'_, W> {
+    fn write(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        let format = &self.config.format;
+        self.writer.write_field(format.date_text(event.date))?;
+        self.writer
+            .write_field(format.duration_text(event.duration.as_micros()))?;
+        self.writer.write_field(event.name)?;
+        self.writer.write_field(event.level.to_string())?;
+        for column in self.config.columns.iter() {
+            let mut values = event
+                .properties
+                .iter()
+                .filter(|(name, _)| name.eq_ignore_ascii_case(column))
+                .map(|(_, value)| value.str());
+            let value = match self.config.duplicates {
+                Duplicates::First => values.next().unwrap_or_default(),
+                Duplicates::Join => values.collect::<Vec<_>>().join("\n").into(),
+            };
+            self.writer.write_field(value.as_bytes())?;
+        }
+        self.writer.write_record(None::<&[u8]>)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+impl<W: Write> IntoWriter<W> for CsvOutput<'_, W> {
+    fn into_writer(self: Box<Self>) -> Result<W, Box<dyn Error>> {
+        Ok(self.writer.into_inner().map_err(|e| e.into_error())?)
+    }
+}
+
+impl<W: Write> Output for LogWriter<W> {
+    fn write(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        Ok(LogWriter::write(self, event)?)
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(self.flush()?)
+    }
+}
+
+impl<W: Write> IntoWriter<W> for LogWriter<W> {
+    fn into_writer(mut self: Box<Self>) -> Result<W, Box<dyn Error>> {
+        self.flush()?;
+        Ok(self.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+    use tech_log_parser::{Event, LogStr};
+
+    use super::{Duplicates, OutputFormat};
+    use crate::Config;
+
+    fn write(format: OutputFormat, config: &Config) -> String {
+        let properties = [
+            ("process", LogStr::new(b"rphost", '\0')),
+            ("Sql", LogStr::new(b"SELECT 1,\n 'a'", '\0')),
+            ("Sql", LogStr::new(b"SELECT 2", '\0')),
+        ];
+        let event = Event {
+            date: NaiveDate::from_ymd_opt(2024, 1, 4)
+                .and_then(|date| date.and_hms_opt(15, 0, 0))
+                .unwrap(),
+            duration: Duration::from_micros(5),
+            name: "CALL",
+            level: 0,
+            properties: &properties[..1],
+        };
+        let mut buf = Vec::new();
+        let mut output = format.create(&mut buf, config).unwrap();
+        output.write(&event).unwrap();
+        output
+            .write(&Event {
+                properties: &properties,
+                ..event
+            })
+            .unwrap();
+        output.finish().unwrap();
+        drop(output);
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn test_json() {
+        let config = Config::default();
+        let event = r#"{"Date":"2024-01-04T15:00:00","Duration":5,"Name":"CALL","Level":0,"Props":[["process","rphost"]]}"#;
+        let json = write(OutputFormat::Json, &config);
+        assert!(json.starts_with(&format!("[{event},{{")));
+        assert!(json.ends_with("]]}]"));
+        let ndjson = write(OutputFormat::Ndjson, &config);
+        assert!(ndjson.starts_with(&format!("{event}\n{{")));
+        assert_eq!(ndjson.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_csv() {
+        let mut config = Config {
+            columns: vec!["sql".to_string(), "Usr".to_string()],
+            ..Config::default()
+        };
+        assert_eq!(
+            write(OutputFormat::Csv, &config),
+            "Date,Duration,Name,Level,sql,Usr\r\n\
+            2024-01-04T15:00:00,5,CALL,0,,\r\n\
+            2024-01-04T15:00:00,5,CALL,0,\"SELECT 1,\n 'a'\",\r\n"
+        );
+        config.duplicates = Duplicates::Join;
+        assert!(
+            write(OutputFormat::Tsv, &config)
+                .ends_with("2024-01-04T15:00:00\t5\tCALL\t0\t\"SELECT 1,\n 'a'\nSELECT 2\"\t\r\n")
+        );
+    }
+}
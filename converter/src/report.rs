@@ -0,0 +1,90 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ErrorFormat {
+    /// `error: path: message`
+    Text,
+    /// One JSON object per line: `{"File":"path","Message":"message"}`
+    Json,
+}
+
+/// An error of processing a file.
+#[derive(Debug)]
+pub struct FileError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl FileError {
+    pub fn new(path: impl AsRef<Path>, error: impl Display) -> Self {
+        Self {
+            path: path.as_ref().to_owned(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+impl Error for FileError {}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct ErrorRecord<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<&'a Path>,
+    message: String,
+}
+
+/// Messages and errors go to stderr, so stdout is left for the data.
+pub struct Report {
+    pub quiet: bool,
+    pub verbose: bool,
+    pub error_format: ErrorFormat,
+}
+
+impl Report {
+    /// Progress and summary, hidden by `--quiet`.
+    pub fn info(&self, message: impl Display) {
+        if !self.quiet {
+            eprintln!("{message}");
+        }
+    }
+
+    /// Details shown with `--verbose`.
+    pub fn verbose(&self, message: impl Display) {
+        if self.verbose {
+            eprintln!("{message}");
+        }
+    }
+
+    pub fn error(&self, error: &(dyn Error + 'static)) {
+        let file_error = error.downcast_ref::<FileError>();
+        match self.error_format {
+            ErrorFormat::Text => eprintln!("error: {error}"),
+            ErrorFormat::Json => {
+                let record = ErrorRecord {
+                    file: file_error.map(|x| x.path.as_path()),
+                    message: match file_error {
+                        Some(x) => x.message.clone(),
+                        None => error.to_string(),
+                    },
+                };
+                if let Ok(json) = serde_json::to_string(&record) {
+                    eprintln!("{json}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::{error::Error, fs, io, path::Path};
+
+use rusqlite::{Connection, params_from_iter, types::Value};
+use tech_log_parser::Event;
+
+use crate::{
+    Config,
+    output::{Duplicates, Output},
+};
+
+const HEADER_FIELDS: [&str; 5] = ["Id", "Date", "Duration", "Name", "Level"];
+
+/// Writes events into a new SQLite database with the tables
+/// `Events(Id, Date, Duration, Name, Level, <columns>)` and `Props(EventId, Name, Value)`.
+/// Properties of `Config::columns` are columns of `Events`, the others are rows of `Props`.
+/// `Date` is the text `YYYY-MM-DD HH:MM:SS.SSSSSS` understood by the SQLite date functions,
+/// `Duration` is in microseconds. Indexes are created when the output is finished.
+pub struct SqliteOutput<'a> {
+    connection: Connection,
+    config: &'a Config,
+    /// Property columns without duplicates.
+    columns: Vec<String>,
+    insert_event: String,
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn column_name(name: &str) -> String {
+    if HEADER_FIELDS.iter().any(|x| x.eq_ignore_ascii_case(name)) {
+        format!("Prop{name}")
+    } else {
+        name.to_string()
+    }
+}
+
+impl<'a> SqliteOutput<'a> {
+    /// Replaces an existing database at `path`.
+    pub fn create(path: &Path, config: &'a Config) -> Result<Self, Box<dyn Error>> {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+            _ => (),
+        }
+        let connection = Connection::open(path)?;
+
+        let mut columns: Vec<String> = Vec::new();
+        for name in config.columns.iter() {
+            if !columns.iter().any(|x| x.eq_ignore_ascii_case(name)) {
+                columns.push(name.clone());
+            }
+        }
+        let quoted: Vec<_> = columns.iter().map(|x| quote(&column_name(x))).collect();
+
+        let mut create = "CREATE TABLE Events (Id INTEGER PRIMARY KEY, Date TEXT NOT NULL, \
+            Duration INTEGER NOT NULL, Name TEXT NOT NULL, Level INTEGER NOT NULL"
+            .to_string();
+        for column in quoted.iter() {
+            create.push_str(&format!(", {column} TEXT"));
+        }
+        create.push_str(");");
+        // the database is written from scratch, a failed conversion is repeated anyway
+        connection.execute_batch(&format!(
+            "PRAGMA journal_mode = OFF;
+            PRAGMA synchronous = OFF;
+            {create}
+            CREATE TABLE Props (EventId INTEGER NOT NULL REFERENCES Events (Id), \
+                Name TEXT NOT NULL, Value TEXT NOT NULL);
+            BEGIN;"
+        ))?;
+
+        let names: String = quoted.iter().map(|x| format!(", {x}")).collect();
+        let values: String = quoted.iter().map(|_| ", ?").collect();
+        Ok(Self {
+            connection,
+            config,
+            columns,
+            insert_event: format!(
+                "INSERT INTO Events (Date, Duration, Name, Level{names}) VALUES (?, ?, ?, ?{values})"
+            ),
+        })
+    }
+}
+
+impl Output for SqliteOutput<'_> {
+    fn write(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        let mut values = vec![
+            Value::Text(event.date.format("%Y-%m-%d %H:%M:%S%.6f").to_string()),
+            Value::Integer(event.duration.as_micros().min(i64::MAX as u128) as i64),
+            Value::Text(event.name.to_string()),
+            Value::Integer(event.level.into()),
+        ];
+        for column in self.columns.iter() {
+            let mut found = event
+                .properties
+                .iter()
+                .filter(|(name, _)| name.eq_ignore_ascii_case(column))
+                .map(|(_, value)| value.str());
+            let value = match self.config.duplicates {
+                Duplicates::First => found.next().map(|x| x.into_owned()),
+                Duplicates::Join => {
+                    let found: Vec<_> = found.collect();
+                    (!found.is_empty()).then(|| found.join("\n"))
+                }
+            };
+            values.push(value.map_or(Value::Null, Value::Text));
+        }
+        self.connection
+            .prepare_cached(&self.insert_event)?
+            .execute(params_from_iter(values))?;
+        let id = self.connection.last_insert_rowid();
+
+        let mut insert_prop = self
+            .connection
+            .prepare_cached("INSERT INTO Props (EventId, Name, Value) VALUES (?, ?, ?)")?;
+        for (name, value) in event.properties.iter() {
+            if !self.columns.iter().any(|x| x.eq_ignore_ascii_case(name)) {
+                insert_prop.execute((id, name, value.str()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.connection.execute_batch(
+            "COMMIT;
+            CREATE INDEX EventsDate ON Events (Date);
+            CREATE INDEX EventsName ON Events (Name);
+            CREATE INDEX EventsDuration ON Events (Duration);
+            CREATE INDEX PropsEventId ON Props (EventId);
+            CREATE INDEX PropsName ON Props (Name);",
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+    use rusqlite::Connection;
+    use tech_log_parser::{Event, LogStr};
+
+    use super::SqliteOutput;
+    use crate::{Config, output::Output};
+
+    #[test]
+    fn test_sqlite() {
+        let path = std::env::temp_dir().join(format!("converter-{}.sqlite", std::process::id()));
+        let config = Config {
+            columns: vec!["usr".to_string(), "Name".to_string(), "Usr".to_string()],
+            ..Config::default()
+        };
+        let properties = [
+            ("Usr", LogStr::new(b"Admin", '\0')),
+            ("Sql", LogStr::new(b"SELECT 1", '\0')),
+        ];
+        let mut output = SqliteOutput::create(&path, &config).unwrap();
+        for properties in [&properties[..], &properties[1..]] {
+            output
+                .write(&Event {
+                    date: NaiveDate::from_ymd_opt(2024, 1, 4)
+                        .and_then(|date| date.and_hms_micro_opt(15, 0, 0, 5))
+                        .unwrap(),
+                    duration: Duration::from_micros(7),
+                    name: "DBMSSQL",
+                    level: 1,
+                    properties,
+                })
+                .unwrap();
+        }
+        output.finish().unwrap();
+        drop(output);
+
+        let connection = Connection::open(&path).unwrap();
+        let rows: Vec<(String, i64, Option<String>)> = connection
+            .prepare("SELECT Date, Duration, Usr FROM Events WHERE PropName IS NULL ORDER BY Id")
+            .unwrap()
+            .query_map((), |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [
+                (
+                    "2024-01-04 15:00:00.000005".to_string(),
+                    7,
+                    Some("Admin".to_string())
+                ),
+                ("2024-01-04 15:00:00.000005".to_string(), 7, None)
+            ]
+        );
+        let sql: i64 = connection
+            .query_row(
+                "SELECT count(*) FROM Props WHERE Name = 'Sql' AND Value = 'SELECT 1'",
+                (),
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(sql, 2);
+        drop(connection);
+        std::fs::remove_file(path).unwrap();
+    }
+}
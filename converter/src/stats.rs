@@ -0,0 +1,106 @@
+use std::{
+    error::Error,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+use tech_log_parser::aggregate::{Aggregator, GroupKey};
+
+use crate::{
+    Config, Input,
+    files::source_path,
+    report::{FileError, Report},
+};
+
+/// Aggregates files in `jobs` threads, every thread takes the next file from the list.
+fn aggregate(
+    input: &Input,
+    config: &Config,
+    files: &[PathBuf],
+    keys: &[GroupKey],
+    jobs: usize,
+    report: &Report,
+) -> Result<Aggregator, FileError> {
+    let next = AtomicUsize::new(0);
+    let result = Mutex::new(Aggregator::new(keys.to_vec()));
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.clamp(1, files.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| -> Result<(), FileError> {
+                    let mut aggregator = Aggregator::new(keys.to_vec());
+                    while let Some(relative) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let source = source_path(&input.source, relative);
+                        report.verbose(source.display());
+                        tech_log_parser::parse_file(&source, &mut |event| {
+                            config.apply(&event, |event| {
+                                aggregator.add(event);
+                                Ok(())
+                            })?;
+                            Ok(true)
+                        })
+                        .map_err(|e| FileError::new(&source, e))?;
+                    }
+                    result.lock().unwrap().merge(&aggregator);
+                    Ok(())
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .try_for_each(|x| x.join().expect("stats worker panicked"))
+    })?;
+
+    Ok(result.into_inner().unwrap())
+}
+
+pub fn run(
+    input: &Input,
+    keys: &[GroupKey],
+    top: Option<usize>,
+    json: bool,
+    jobs: usize,
+    report: &Report,
+) -> Result<(), Box<dyn Error>> {
+    let config = input.config()?;
+    let files = input.files()?;
+    let aggregator = aggregate(input, &config, &files, keys, jobs, report)?;
+
+    let mut results = aggregator.results();
+    if let Some(top) = top {
+        results.truncate(top);
+    }
+
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    if json {
+        serde_json::to_writer_pretty(&mut out, &results)?;
+        writeln!(out)?;
+    } else {
+        writeln!(
+            out,
+            "{:>10} {:>14} {:>10} {:>10} {:>10} {:>10} {:>10}  Key",
+            "Count", "Total", "Avg", "Max", "P50", "P95", "P99"
+        )?;
+        for x in results.iter() {
+            let key = x.key.join(" | ").replace(['\r', '\n'], " ");
+            writeln!(
+                out,
+                "{:>10} {:>14} {:>10} {:>10} {:>10} {:>10} {:>10}  {key}",
+                x.count, x.total, x.avg, x.max, x.p50, x.p95, x.p99
+            )?;
+        }
+    }
+    out.flush()?;
+
+    report.info(format_args!(
+        "files: {}, groups: {}",
+        files.len(),
+        aggregator.groups().len()
+    ));
+    Ok(())
+}
@@ -0,0 +1,21 @@
+#![no_main]
+
+use chrono::NaiveDate;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let date = NaiveDate::from_ymd_opt(2024, 1, 4)
+        .and_then(|date| date.and_hms_opt(15, 0, 0))
+        .unwrap();
+
+    let mut count = 0usize;
+    let (cont, read) = tech_log_parser::parse_buffer(data, date, &mut |event| {
+        assert!(event.date.date() == date.date());
+        count += 1;
+        Ok(true)
+    })
+    .unwrap();
+    assert!(cont);
+    assert!(read <= data.len());
+    assert!(count <= data.len());
+});
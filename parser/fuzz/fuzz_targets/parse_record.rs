@@ -0,0 +1,24 @@
+#![no_main]
+
+use chrono::NaiveDate;
+use libfuzzer_sys::fuzz_target;
+use tech_log_parser::{ParseError, Parser};
+
+fuzz_target!(|data: &[u8]| {
+    let date = NaiveDate::from_ymd_opt(2024, 1, 4)
+        .and_then(|date| date.and_hms_opt(15, 0, 0))
+        .unwrap();
+
+    let mut parser = Parser::new(data);
+    match tech_log_parser::parse_record(&mut parser, date) {
+        Ok(event) => {
+            assert!(!event.properties.is_empty());
+            for (_, value) in event.properties {
+                let _ = value.str();
+            }
+        }
+        Err(ParseError::InvalidFormat(position)) => assert!(position <= data.len()),
+        Err(ParseError::End) => (),
+    }
+    assert!(parser.position() <= data.len());
+});
@@ -0,0 +1,324 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Event, context::Context, sql};
+
+/// A part of the group key.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub enum GroupKey {
+    Name,
+    Level,
+    /// Value of the first property with the name, empty if missing.
+    Prop(String),
+    /// `sql::fingerprint` of the `Sql` property, as a hex hash.
+    SqlFingerprint,
+    /// `sql::normalize` of the `Sql` property.
+    SqlText,
+    /// `Context::top` of the `Context` property.
+    ContextTop,
+    /// `Context::entry` of the `Context` property.
+    ContextEntry,
+}
+
+impl GroupKey {
+    pub fn value(&self, event: &Event) -> String {
+        let mut value = String::new();
+        self.write_value(event, &mut value);
+        value
+    }
+
+    /// Appends the value to `out`, so a buffer can be reused for every event.
+    pub fn write_value(&self, event: &Event, out: &mut String) {
+        let prop = |name| event.get_prop(name).map(|x| x.str());
+        match self {
+            GroupKey::Name => out.push_str(event.name),
+            GroupKey::Level => {
+                let _ = write!(out, "{}", event.level);
+            }
+            GroupKey::Prop(name) => out.push_str(&prop(name).unwrap_or_default()),
+            GroupKey::SqlFingerprint => {
+                if let Some(sql) = prop("Sql") {
+                    let _ = write!(out, "{}", sql::fingerprint(&sql));
+                }
+            }
+            GroupKey::SqlText => {
+                if let Some(sql) = prop("Sql") {
+                    out.push_str(&sql::normalize(&sql));
+                }
+            }
+            GroupKey::ContextTop => {
+                if let Some(context) = event.get_prop("Context").map(Context::new)
+                    && let Some(frame) = context.top()
+                {
+                    let _ = write!(out, "{frame}");
+                }
+            }
+            GroupKey::ContextEntry => {
+                if let Some(context) = event.get_prop("Context").map(Context::new)
+                    && let Some(frame) = context.entry()
+                {
+                    let _ = write!(out, "{frame}");
+                }
+            }
+        }
+    }
+}
+
+/// Streaming quantile sketch with relative accuracy: values are counted in logarithmic buckets,
+/// so a quantile is within 1% of the exact value and memory depends on the value range only.
+/// The minimum and the maximum are exact, other quantiles are clamped to them.
+#[derive(Clone, Debug, Default)]
+pub struct Sketch {
+    zeros: u64,
+    buckets: BTreeMap<i32, u64>,
+    count: u64,
+    min: u64,
+    max: u64,
+}
+
+const SKETCH_ACCURACY: f64 = 0.01;
+const SKETCH_GAMMA: f64 = (1.0 + SKETCH_ACCURACY) / (1.0 - SKETCH_ACCURACY);
+
+impl Sketch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, value: u64) {
+        self.min = if self.count == 0 {
+            value
+        } else {
+            self.min.min(value)
+        };
+        self.max = self.max.max(value);
+        self.count += 1;
+        if value == 0 {
+            self.zeros += 1;
+        } else {
+            let index = (value as f64).ln() / SKETCH_GAMMA.ln();
+            *self.buckets.entry(index.ceil() as i32).or_default() += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &Sketch) {
+        if other.count == 0 {
+            return;
+        }
+        self.min = if self.count == 0 {
+            other.min
+        } else {
+            self.min.min(other.min)
+        };
+        self.max = self.max.max(other.max);
+        self.count += other.count;
+        self.zeros += other.zeros;
+        for (index, count) in other.buckets.iter() {
+            *self.buckets.entry(*index).or_default() += count;
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// `q` from 0.0 to 1.0, returns 0 for an empty sketch.
+    pub fn quantile(&self, q: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let rank = (q.clamp(0.0, 1.0) * (self.count - 1) as f64) as u64;
+        if rank == 0 {
+            return self.min;
+        }
+        if rank == self.count - 1 {
+            return self.max;
+        }
+        if rank < self.zeros {
+            return 0;
+        }
+        let mut seen = self.zeros;
+        for (index, count) in self.buckets.iter() {
+            seen += count;
+            if seen > rank {
+                let value = 2.0 * SKETCH_GAMMA.powi(*index) / (SKETCH_GAMMA + 1.0);
+                return (value.round() as u64).clamp(self.min, self.max);
+            }
+        }
+        0
+    }
+}
+
+/// Duration statistics of a group, in microseconds.
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    pub count: u64,
+    pub total: u128,
+    pub max: u64,
+    pub sketch: Sketch,
+}
+
+impl Stats {
+    pub fn add(&mut self, duration: u64) {
+        self.count += 1;
+        self.total += duration as u128;
+        self.max = self.max.max(duration);
+        self.sketch.insert(duration);
+    }
+
+    pub fn merge(&mut self, other: &Stats) {
+        self.count += other.count;
+        self.total += other.total;
+        self.max = self.max.max(other.max);
+        self.sketch.merge(&other.sketch);
+    }
+
+    pub fn avg(&self) -> u64 {
+        if self.count == 0 {
+            0
+        } else {
+            (self.total / self.count as u128) as u64
+        }
+    }
+}
+
+/// A row of `Aggregator::results`, durations are in microseconds.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GroupStats {
+    pub key: Vec<String>,
+    pub count: u64,
+    pub total: u128,
+    pub avg: u64,
+    pub max: u64,
+    pub p50: u64,
+    pub p95: u64,
+    pub p99: u64,
+}
+
+/// Groups events by `keys` and collects duration statistics of every group.
+pub struct Aggregator {
+    keys: Vec<GroupKey>,
+    groups: HashMap<Vec<String>, Stats>,
+}
+
+impl Aggregator {
+    pub fn new(keys: Vec<GroupKey>) -> Self {
+        Self {
+            keys,
+            groups: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, event: &Event) {
+        let key: Vec<_> = self.keys.iter().map(|x| x.value(event)).collect();
+        let duration = event.duration.as_micros().min(u64::MAX as u128) as u64;
+        self.groups.entry(key).or_default().add(duration);
+    }
+
+    /// Merges results of another aggregator with the same keys, e.g. one that processed other files
+    /// in a parallel thread.
+    pub fn merge(&mut self, other: &Aggregator) {
+        for (key, stats) in other.groups.iter() {
+            self.groups.entry(key.clone()).or_default().merge(stats);
+        }
+    }
+
+    pub fn groups(&self) -> &HashMap<Vec<String>, Stats> {
+        &self.groups
+    }
+
+    /// Statistics of all groups, sorted by the total duration descending.
+    pub fn results(&self) -> Vec<GroupStats> {
+        let mut results: Vec<_> = self
+            .groups
+            .iter()
+            .map(|(key, stats)| GroupStats {
+                key: key.clone(),
+                count: stats.count,
+                total: stats.total,
+                avg: stats.avg(),
+                max: stats.max,
+                p50: stats.sketch.quantile(0.5),
+                p95: stats.sketch.quantile(0.95),
+                p99: stats.sketch.quantile(0.99),
+            })
+            .collect();
+        results.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.key.cmp(&b.key)));
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+
+    use super::{Aggregator, GroupKey, Sketch};
+    use crate::{Event, LogStr};
+
+    #[test]
+    fn test_sketch() {
+        let mut sketch = Sketch::new();
+        for i in 0..=1000 {
+            sketch.insert(i);
+        }
+        for (q, exact) in [(0.5, 500.0), (0.95, 950.0), (0.99, 990.0), (1.0, 1000.0)] {
+            let value = sketch.quantile(q) as f64;
+            assert!((value - exact).abs() <= exact * 0.011, "{q}: {value}");
+        }
+        assert_eq!(sketch.quantile(0.0), 0);
+        assert_eq!(Sketch::new().quantile(0.5), 0);
+
+        // the value of the bucket of 1001 is 1002
+        let mut sketch = Sketch::new();
+        sketch.insert(1001);
+        assert_eq!(sketch.quantile(1.0), 1001);
+        let mut merged = Sketch::new();
+        merged.merge(&Sketch::new());
+        merged.merge(&sketch);
+        merged.insert(1003);
+        assert_eq!(merged.count(), 2);
+        assert_eq!(merged.quantile(0.0), 1001);
+        assert_eq!(merged.quantile(1.0), 1003);
+        merged.insert(1005);
+        assert_eq!(merged.quantile(0.5), 1002);
+    }
+
+    #[test]
+    fn test_aggregator() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 4)
+            .and_then(|date| date.and_hms_opt(15, 0, 0))
+            .unwrap();
+        let keys: Vec<GroupKey> = serde_json::from_str(r#"["Name", "SqlFingerprint"]"#).unwrap();
+        let mut aggregator = Aggregator::new(keys);
+        for (name, sql, duration) in [
+            ("DBMSSQL", "SELECT 1 FROM T WHERE x = 1", 100),
+            ("DBMSSQL", "select 1\r\nfrom t where x = 25", 300),
+            ("DBMSSQL", "SELECT 2 FROM T2", 50),
+            ("CALL", "", 1000),
+        ] {
+            let properties = [("Sql", LogStr::new(sql.as_bytes(), '\0'))];
+            aggregator.add(&Event {
+                date,
+                duration: Duration::from_micros(duration),
+                name,
+                level: 0,
+                properties: &properties[..if sql.is_empty() { 0 } else { 1 }],
+            });
+        }
+
+        let results = aggregator.results();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].key, ["CALL", ""]);
+        assert_eq!(results[1].count, 2);
+        assert_eq!(results[1].total, 400);
+        assert_eq!(results[1].avg, 200);
+        assert_eq!(results[1].max, 300);
+        assert_eq!(results[2].total, 50);
+    }
+}
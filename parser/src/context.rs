@@ -0,0 +1,152 @@
+use std::{borrow::Cow, fmt};
+
+use crate::LogStr;
+
+/// A line of the `Context` property: `ОбщийМодуль.Сервер.Модуль : 123 : Запрос.Выполнить();`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame<'s> {
+    /// Module path, e.g. `ОбщийМодуль.Сервер.Модуль`.
+    pub module: &'s str,
+    /// Missing for the lines that describe the call itself, like `Форма.Вызов : ОбщаяФорма.Форма.Модуль.Команда`.
+    pub line: Option<u32>,
+    /// Source line, or the rest of the text when there is no line number.
+    pub code: &'s str,
+}
+
+impl<'s> Frame<'s> {
+    pub fn parse(text: &'s str) -> Frame<'s> {
+        let text = text.trim();
+        let Some((module, rest)) = text.split_once(" : ") else {
+            return Frame {
+                module: text,
+                line: None,
+                code: "",
+            };
+        };
+        let (line, code) = rest.split_once(" : ").unwrap_or((rest, ""));
+        match line.trim().parse() {
+            Ok(line) => Frame {
+                module: module.trim(),
+                line: Some(line),
+                code: code.trim(),
+            },
+            Err(_) => Frame {
+                module: module.trim(),
+                line: None,
+                code: rest.trim(),
+            },
+        }
+    }
+}
+
+/// Writes the frame with whitespace of the source line collapsed, so frames that differ only in
+/// indentation are equal.
+impl fmt::Display for Frame<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.module)?;
+        if let Some(line) = self.line {
+            write!(f, " : {line}")?;
+        }
+        for (i, word) in self.code.split_whitespace().enumerate() {
+            f.write_str(if i == 0 { " : " } else { " " })?;
+            f.write_str(word)?;
+        }
+        Ok(())
+    }
+}
+
+/// Call stack from the `Context` property of CALL, SDBL, DBMSSQL, TLOCK and other events.
+/// The first line is the entry point, the last line is the code that produced the event.
+/// The text is borrowed from the property unless its doubled quotes have to be replaced,
+/// frames borrow the context, nothing else is copied until `normalized` is called.
+#[derive(Clone, Debug)]
+pub struct Context<'s> {
+    text: Cow<'s, str>,
+}
+
+impl<'s> Context<'s> {
+    pub fn new(value: &LogStr<'s>) -> Context<'s> {
+        Context { text: value.str() }
+    }
+
+    pub fn frames(&self) -> impl DoubleEndedIterator<Item = Frame<'_>> {
+        self.text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(Frame::parse)
+    }
+
+    /// The outermost frame, where the server call or the background job started.
+    pub fn entry(&self) -> Option<Frame<'_>> {
+        self.frames().next()
+    }
+
+    /// The innermost frame, the code that produced the event.
+    pub fn top(&self) -> Option<Frame<'_>> {
+        self.frames().next_back()
+    }
+
+    /// The stack as text suitable for grouping: one frame per line, whitespace collapsed.
+    pub fn normalized(&self) -> String {
+        let mut result = String::with_capacity(self.text.len());
+        for (i, frame) in self.frames().enumerate() {
+            if i > 0 {
+                result.push('\n');
+            }
+            result.push_str(&frame.to_string());
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::{Context, Frame};
+    use crate::LogStr;
+
+    const CONTEXT: &str = "Форма.Вызов : ОбщаяФорма.Отчет.Модуль.Сформировать\r\n\
+        ОбщаяФорма.Отчет.Форма : 15 : Результат = Сервер.Выполнить(Параметры);\r\n\
+        \tОбщийМодуль.Сервер.Модуль : 123 :   Выборка  =  Запрос.Выполнить().Выбрать();\r\n";
+
+    #[test]
+    fn test_frames() {
+        let context = Context::new(&LogStr::new(CONTEXT.as_bytes(), '\0'));
+        assert!(matches!(context.text, Cow::Borrowed(_)));
+        assert_eq!(context.frames().count(), 3);
+        assert_eq!(
+            context.entry(),
+            Some(Frame {
+                module: "Форма.Вызов",
+                line: None,
+                code: "ОбщаяФорма.Отчет.Модуль.Сформировать",
+            })
+        );
+        assert_eq!(
+            context.top(),
+            Some(Frame {
+                module: "ОбщийМодуль.Сервер.Модуль",
+                line: Some(123),
+                code: "Выборка  =  Запрос.Выполнить().Выбрать();",
+            })
+        );
+    }
+
+    #[test]
+    fn test_normalized() {
+        let context = Context::new(&LogStr::new(CONTEXT.as_bytes(), '\0'));
+        assert_eq!(
+            context.normalized(),
+            "Форма.Вызов : ОбщаяФорма.Отчет.Модуль.Сформировать\n\
+            ОбщаяФорма.Отчет.Форма : 15 : Результат = Сервер.Выполнить(Параметры);\n\
+            ОбщийМодуль.Сервер.Модуль : 123 : Выборка = Запрос.Выполнить().Выбрать();"
+        );
+        assert_eq!(Frame::parse("Модуль : 1 : А = Б : В").code, "А = Б : В");
+        assert!(Context::new(&LogStr::new(b"", '\0')).top().is_none());
+
+        let quoted = LogStr::new(b"\xd0\x9c : 1 : A = ''b'';", '\'');
+        let context = Context::new(&quoted);
+        assert_eq!(context.normalized(), "М : 1 : A = 'b';");
+    }
+}
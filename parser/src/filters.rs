@@ -0,0 +1,345 @@
+use chrono::{NaiveDateTime, NaiveTime};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, de::Visitor};
+
+use crate::{Event, LogStr};
+
+#[derive(Deserialize)]
+pub enum StrFilter {
+    /// Case sensitive.
+    Equal(String),
+    /// Made by `StrFilter::equal_ignore_case` or deserialized from any string.
+    EqualIgnoreCase(Lowercase),
+    Contains(String),
+    StartsWith(String),
+    EndsWith(String),
+    Match(Match),
+    InList(Vec<String>),
+}
+
+/// A string lowercased once, when the filter is made, instead of every check.
+pub struct Lowercase(String);
+
+impl Lowercase {
+    fn new(s: &str) -> Self {
+        Lowercase(s.chars().flat_map(char::to_lowercase).collect())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<'de> Deserialize<'de> for Lowercase {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(|s| Lowercase::new(&s))
+    }
+}
+
+impl StrFilter {
+    pub fn equal_ignore_case(s: &str) -> Self {
+        StrFilter::EqualIgnoreCase(Lowercase::new(s))
+    }
+
+    pub fn check(&self, value: &str) -> bool {
+        match self {
+            StrFilter::Equal(s) => s == value,
+            StrFilter::EqualIgnoreCase(Lowercase(s)) => {
+                value.eq_ignore_ascii_case(s)
+                    || value.chars().flat_map(char::to_lowercase).eq(s.chars())
+            }
+            StrFilter::Contains(s) => value.contains(s),
+            StrFilter::StartsWith(s) => value.starts_with(s),
+            StrFilter::EndsWith(s) => value.ends_with(s),
+            StrFilter::Match(m) => m.0.is_match(value),
+            StrFilter::InList(s) => s.iter().any(|x| x == value),
+        }
+    }
+}
+
+// performance is better with Box
+pub struct Match(Box<Regex>);
+
+impl Match {
+    /// Case insensitive, the dot matches a line break too.
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        RegexBuilder::new(pattern)
+            .crlf(true)
+            .case_insensitive(true)
+            .dot_matches_new_line(true)
+            .build()
+            .map(|regex| Match(Box::new(regex)))
+    }
+}
+
+impl<'de> Deserialize<'de> for Match {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_string(MatchVisitor {})
+    }
+}
+
+struct MatchVisitor {}
+
+impl Visitor<'_> for MatchVisitor {
+    type Value = Match;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a regular expression")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        Match::new(v).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PropFilter {
+    pub name: String,
+    pub filter: StrFilter,
+}
+
+impl PropFilter {
+    pub fn check(&self, props: &[(&str, LogStr<'_>)]) -> bool {
+        for (name, value) in props {
+            if name.eq_ignore_ascii_case(&self.name) && self.filter.check(&value.str()) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Numeric property filter, the property value is parsed as a floating point number,
+/// e.g. `Rows` or `MemoryPeak`.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PropNumFilter {
+    pub name: String,
+    pub filter: NumFilter,
+}
+
+impl PropNumFilter {
+    pub fn check(&self, props: &[(&str, LogStr<'_>)]) -> bool {
+        for (name, value) in props {
+            if name.eq_ignore_ascii_case(&self.name)
+                && let Ok(value) = value.str().trim().parse()
+                && self.filter.check(value)
+            {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[derive(Deserialize)]
+pub enum NumFilter {
+    Equal(f64),
+    Greater(f64),
+    GreaterOrEqual(f64),
+    Less(f64),
+    LessOrEqual(f64),
+}
+
+impl NumFilter {
+    pub fn check(&self, value: f64) -> bool {
+        match self {
+            NumFilter::Equal(x) => value == *x,
+            NumFilter::Greater(x) => value > *x,
+            NumFilter::GreaterOrEqual(x) => value >= *x,
+            NumFilter::Less(x) => value < *x,
+            NumFilter::LessOrEqual(x) => value <= *x,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub enum DataFilter {
+    GreaterOrEqual(NaiveDateTime),
+    LessOrEqual(NaiveDateTime),
+    Greater(NaiveDateTime),
+    Less(NaiveDateTime),
+}
+
+impl DataFilter {
+    pub fn check(&self, value: &NaiveDateTime) -> bool {
+        match self {
+            DataFilter::GreaterOrEqual(date) => value >= date,
+            DataFilter::LessOrEqual(date) => value <= date,
+            DataFilter::Greater(date) => value > date,
+            DataFilter::Less(date) => value < date,
+        }
+    }
+}
+
+/// Time of day of the event, e.g. `{"GreaterOrEqual":"09:00:00"}`.
+#[derive(Deserialize)]
+pub enum TimeFilter {
+    GreaterOrEqual(NaiveTime),
+    LessOrEqual(NaiveTime),
+    Greater(NaiveTime),
+    Less(NaiveTime),
+}
+
+impl TimeFilter {
+    pub fn check(&self, value: &NaiveTime) -> bool {
+        match self {
+            TimeFilter::GreaterOrEqual(time) => value >= time,
+            TimeFilter::LessOrEqual(time) => value <= time,
+            TimeFilter::Greater(time) => value > time,
+            TimeFilter::Less(time) => value < time,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub enum DurationFilter {
+    GreaterOrEqual(u128),
+    LessOrEqual(u128),
+    Greater(u128),
+    Less(u128),
+}
+
+impl DurationFilter {
+    pub fn check(&self, value: &u128) -> bool {
+        match self {
+            DurationFilter::GreaterOrEqual(dur) => value >= dur,
+            DurationFilter::LessOrEqual(dur) => value <= dur,
+            DurationFilter::Greater(dur) => value > dur,
+            DurationFilter::Less(dur) => value < dur,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub enum Filter {
+    Date(DataFilter),
+    Time(TimeFilter),
+    Duration(DurationFilter),
+    Name(StrFilter),
+    Level(NumFilter),
+    Prop(PropFilter),
+    PropNum(PropNumFilter),
+    /// The event has a property with the name, case insensitive.
+    HasProp(String),
+    /// All of the filters, `true` if empty.
+    And(Vec<Filter>),
+    /// Any of the filters, `false` if empty.
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    pub fn check(&self, event: &Event) -> bool {
+        match self {
+            Filter::Date(filter) => filter.check(&event.date),
+            Filter::Time(filter) => filter.check(&event.date.time()),
+            Filter::Duration(filter) => filter.check(&event.duration.as_micros()),
+            Filter::Name(filter) => filter.check(event.name),
+            Filter::Level(filter) => filter.check(event.level as f64),
+            Filter::Prop(filter) => filter.check(event.properties),
+            Filter::PropNum(filter) => filter.check(event.properties),
+            Filter::HasProp(name) => event.get_prop(name).is_some(),
+            Filter::And(filters) => filters.iter().all(|x| x.check(event)),
+            Filter::Or(filters) => filters.iter().any(|x| x.check(event)),
+            Filter::Not(filter) => !filter.check(event),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+
+    use super::{Filter, StrFilter};
+    use crate::{Event, LogStr};
+
+    #[test]
+    fn test_composition() {
+        let filter: Filter = serde_json::from_str(
+            r#"{"Or":[
+                {"Name":{"Equal":"EXCP"}},
+                {"And":[{"Name":{"Equal":"DBMSSQL"}},{"Not":{"Duration":{"LessOrEqual":1000000}}}]}
+            ]}"#,
+        )
+        .unwrap();
+        let check = |name, duration| {
+            filter.check(&Event {
+                date: NaiveDate::from_ymd_opt(2024, 1, 4)
+                    .and_then(|date| date.and_hms_opt(15, 0, 0))
+                    .unwrap(),
+                duration: Duration::from_micros(duration),
+                name,
+                level: 0,
+                properties: &[],
+            })
+        };
+        assert!(check("EXCP", 0));
+        assert!(check("DBMSSQL", 1_000_001));
+        assert!(!check("DBMSSQL", 1_000_000));
+        assert!(!check("CALL", 2_000_000));
+    }
+
+    #[test]
+    fn test_predicates() {
+        let properties = [
+            ("Rows", LogStr::new(b"10001", '\0')),
+            ("MemoryPeak", LogStr::new(b"1.5e9", '\0')),
+            ("Usr", LogStr::new(b"Admin", '\0')),
+        ];
+        let event = Event {
+            date: NaiveDate::from_ymd_opt(2024, 1, 4)
+                .and_then(|date| date.and_hms_opt(9, 0, 0))
+                .unwrap(),
+            duration: Duration::from_micros(10),
+            name: "DBMSSQL",
+            level: 3,
+            properties: &properties,
+        };
+        let check = |json: &str| serde_json::from_str::<Filter>(json).unwrap().check(&event);
+
+        assert!(check(r#"{"Level":{"GreaterOrEqual":3}}"#));
+        assert!(!check(r#"{"Level":{"Less":3}}"#));
+        assert!(check(
+            r#"{"PropNum":{"Name":"rows","Filter":{"Greater":10000}}}"#
+        ));
+        assert!(check(
+            r#"{"PropNum":{"Name":"MemoryPeak","Filter":{"GreaterOrEqual":1e9}}}"#
+        ));
+        assert!(!check(
+            r#"{"PropNum":{"Name":"Usr","Filter":{"Less":1e9}}}"#
+        ));
+        assert!(check(r#"{"HasProp":"usr"}"#));
+        assert!(check(r#"{"Not":{"HasProp":"Context"}}"#));
+        assert!(check(
+            r#"{"Date":{"Greater":"2024-01-04T08:59:59.999999"}}"#
+        ));
+        assert!(!check(r#"{"Date":{"Less":"2024-01-04T09:00:00"}}"#));
+        assert!(check(r#"{"Time":{"GreaterOrEqual":"09:00:00"}}"#));
+        assert!(!check(r#"{"Time":{"Greater":"09:00:00"}}"#));
+        assert!(check(r#"{"Duration":{"Less":11}}"#));
+        assert!(check(r#"{"Name":{"StartsWith":"DBMS"}}"#));
+        assert!(check(r#"{"Name":{"EndsWith":"SQL"}}"#));
+        assert!(!check(r#"{"Name":{"Equal":"dbmssql"}}"#));
+        assert!(check(r#"{"Name":{"EqualIgnoreCase":"dbmssql"}}"#));
+        assert!(check(r#"{"Name":{"EqualIgnoreCase":"DbmsSql"}}"#));
+
+        let filter = StrFilter::equal_ignore_case("Администратор");
+        assert!(filter.check("Администратор"));
+        assert!(filter.check("АДМИНИСТРАТОР"));
+        assert!(filter.check("администратор"));
+        assert!(!filter.check("администратор1"));
+    }
+}
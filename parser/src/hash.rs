@@ -0,0 +1,34 @@
+/// 64-bit FNV-1a, unlike `DefaultHasher` the result is stable between Rust releases,
+/// so it can be stored or compared across runs.
+#[derive(Clone, Copy)]
+pub(crate) struct Fnv64(u64);
+
+impl Fnv64 {
+    pub(crate) fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) -> &mut Self {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        self
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Fnv64;
+
+    #[test]
+    fn test_fnv() {
+        assert_eq!(Fnv64::new().finish(), 0xcbf29ce484222325);
+        assert_eq!(Fnv64::new().write(b"a").finish(), 0xaf63dc4c8601ec8c);
+        assert_eq!(Fnv64::new().write(b"foobar").finish(), 0x85944171f73967e8);
+    }
+}
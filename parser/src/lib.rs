@@ -1,26 +1,58 @@
-use std::{io::Read, path::Path, time::Duration};
+use std::{
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+    time::Duration,
+};
 
 use chrono::{NaiveDate, NaiveDateTime, Timelike};
-use parser::Parser;
 
+pub mod aggregate;
+pub mod context;
+pub mod filters;
+mod hash;
 mod io;
+pub mod lock_graph;
+pub mod locks;
 mod parser;
+pub mod query;
+mod reader;
+pub mod redact;
+mod serialize;
+pub mod sql;
+pub mod timeseries;
+pub mod top;
 mod types;
 mod worker;
+mod writer;
 
 pub use parser::EndError;
 pub use parser::ParseError;
 pub use parser::ParseResult;
+pub use parser::Parser;
+pub use reader::EventReader;
+pub use serialize::DateFormat;
+pub use serialize::DurationFormat;
+pub use serialize::EventSerializer;
+pub use serialize::PropsFormat;
+pub use serialize::SerializeOptions;
 pub use types::Event;
 pub use types::LogStr;
+pub use types::OwnedEvent;
+pub use writer::HourlyLogWriter;
+pub use writer::LogWriter;
+pub use writer::log_file_name;
 
 use crate::io::open_file;
 
 pub fn parse_record<'a>(parser: &'a mut Parser, date: NaiveDateTime) -> ParseResult<Event<'a>> {
     parser.prop_buf.clear();
-    let min = parser.parse_number(':')?;
-    let sec = parser.parse_number('.')?;
+    let start = parser.position();
+    let min: u32 = parser.parse_number(':')?;
+    let sec: u32 = parser.parse_number('.')?;
     let msec: u32 = parser.parse_number('-')?;
+    if min > 59 || sec > 59 || msec > 999_999 {
+        return Err(ParseError::InvalidFormat(start));
+    }
     let duration = parser.parse_number(',')?;
     let name = parser.parse_name(',')?;
     let level = parser.parse_number(',')?;
@@ -38,10 +70,9 @@ pub fn parse_record<'a>(parser: &'a mut Parser, date: NaiveDateTime) -> ParseRes
 
     let date = date
         .with_minute(min)
-        .and_then(|date| date.with_minute(min))
         .and_then(|date| date.with_second(sec))
         .and_then(|date| date.with_nanosecond(msec * 1000))
-        .ok_or(ParseError::InvalidFormat)?;
+        .ok_or(ParseError::InvalidFormat(start))?;
     let properties = parser.prop_buf.as_slice();
 
     Ok(Event {
@@ -71,7 +102,7 @@ where
                 }
             }
             Err(ParseError::End) => return Ok((true, position)),
-            Err(ParseError::InvalidFormat) => {
+            Err(ParseError::InvalidFormat(_)) => {
                 if parser.skip_to(b'\n').is_err() {
                     return Ok((true, position));
                 }
@@ -80,7 +111,8 @@ where
     }
 }
 
-fn parse_date_file(file_name: impl AsRef<Path>) -> Option<NaiveDateTime> {
+/// Start of the hour of a log file named `yymmddhh.log`.
+pub fn parse_date_file(file_name: impl AsRef<Path>) -> Option<NaiveDateTime> {
     let name = Path::new(file_name.as_ref()).file_stem()?.to_str()?;
     if name.len() < 8 {
         return None;
@@ -101,6 +133,22 @@ fn parse_date_file(file_name: impl AsRef<Path>) -> Option<NaiveDateTime> {
 }
 
 pub fn parse_file<F, P>(file_name: P, action: &mut F) -> Result<(), Box<dyn std::error::Error>>
+where
+    F: FnMut(Event) -> Result<bool, Box<dyn std::error::Error>>,
+    P: AsRef<Path>,
+{
+    parse_file_from(file_name, 0, action)?;
+    Ok(())
+}
+
+/// Parses the file starting at the byte offset `start`, which must be the beginning of a record
+/// (or 0), and returns the offset after the last complete record. An incomplete record at the end
+/// of a file that is still being written is left for the next call.
+pub fn parse_file_from<F, P>(
+    file_name: P,
+    start: u64,
+    action: &mut F,
+) -> Result<u64, Box<dyn std::error::Error>>
 where
     F: FnMut(Event) -> Result<bool, Box<dyn std::error::Error>>,
     P: AsRef<Path>,
@@ -108,6 +156,10 @@ where
     let date = parse_date_file(&file_name).ok_or("invalid file name")?;
 
     let mut file = open_file(file_name)?;
+    if start > 0 {
+        file.seek(SeekFrom::Start(start))?;
+    }
+    let mut position = file.stream_position()?;
 
     let mut buffer = vec![0u8; 1024 * 1024];
     let mut offset = 0usize;
@@ -120,6 +172,7 @@ where
         let len = len + offset;
 
         let (cont, read) = parse_buffer(&buffer[0..len], date, action)?;
+        position += read as u64;
         if !cont {
             break;
         }
@@ -134,7 +187,7 @@ where
         offset = len - read;
     }
 
-    Ok(())
+    Ok(position)
 }
 
 pub fn parse_file_with_worker<F, P>(
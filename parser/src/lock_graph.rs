@@ -0,0 +1,534 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt::Write,
+};
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::{
+    Event,
+    locks::{Lock, LockEvent, lock_tokens, parse_lock_tokens},
+};
+
+/// Locks granted to a connection that are remembered to find what a blocker held.
+const HELD_LOCKS_LIMIT: usize = 64;
+/// Protection from malformed logs, real chains are much shorter.
+const CHAIN_LIMIT: usize = 32;
+
+/// Connection numbers are unique within an infobase only.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Connection {
+    /// From `p:processName`.
+    pub infobase: String,
+    /// From `t:connectID`.
+    pub id: u64,
+}
+
+/// A lock granted to a connection.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct HeldLock {
+    pub date: NaiveDateTime,
+    pub lock: Lock,
+    pub context: Option<String>,
+}
+
+/// A TLOCK or TTIMEOUT event that waited for other connections.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Wait {
+    pub connection: Connection,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub event: String,
+    pub wait_connections: Vec<u64>,
+    pub regions: Vec<String>,
+    pub locks: Vec<Lock>,
+    pub context: Option<String>,
+}
+
+/// Who-waited-on-whom chain started by a single wait.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct WaitChain {
+    /// The first element is the wait the chain is built for, every next one is a wait of a
+    /// connection the previous one waited for, at the same time.
+    pub waits: Vec<Wait>,
+    /// The connection at the end of the chain that did not wait itself.
+    pub blocker: Option<Connection>,
+    /// The lock of `blocker` that conflicts with the last wait, if it was logged.
+    pub blocker_lock: Option<HeldLock>,
+    /// The chain returned to one of its connections.
+    pub cycle: bool,
+}
+
+/// One element of `DeadlockConnectionIntersections`: `waiter` requested `locks` held by `holder`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Intersection {
+    pub waiter: u64,
+    pub holder: u64,
+    pub locks: Vec<Lock>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Deadlock {
+    pub date: NaiveDateTime,
+    /// The connection whose transaction was chosen as the victim.
+    pub connection: Connection,
+    pub intersections: Vec<Intersection>,
+    pub context: Option<String>,
+}
+
+/// Parses `DeadlockConnectionIntersections` of TDEADLOCK:
+/// `24 25 AccumRg17.DIMS Exclusive Fld18=1, 25 24 InfoRg5.DIMS Exclusive Fld6=2`.
+pub fn parse_intersections(text: &str) -> Vec<Intersection> {
+    let tokens = lock_tokens(text);
+    let is_pair = |i: usize| {
+        i + 1 < tokens.len()
+            && tokens[i].parse::<u64>().is_ok()
+            && tokens[i + 1].parse::<u64>().is_ok()
+    };
+
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        if !is_pair(i) {
+            i += 1;
+            continue;
+        }
+        let start = i + 2;
+        let mut end = start;
+        while end < tokens.len() && !is_pair(end) {
+            end += 1;
+        }
+        result.push(Intersection {
+            waiter: tokens[i].parse().unwrap_or_default(),
+            holder: tokens[i + 1].parse().unwrap_or_default(),
+            locks: parse_lock_tokens(&tokens[start..end]),
+        });
+        i = end;
+    }
+    result
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LockGraphReport {
+    pub chains: Vec<WaitChain>,
+    pub deadlocks: Vec<Deadlock>,
+}
+
+impl LockGraphReport {
+    /// Graphviz representation: a node per connection, an edge per wait, deadlocks in red.
+    pub fn to_dot(&self) -> String {
+        let node = |c: &Connection| format!("\"{}:{}\"", c.infobase.replace('"', "\\\""), c.id);
+        let mut edges = BTreeMap::<(String, String), (String, &str)>::new();
+        for chain in self.chains.iter() {
+            for wait in chain.waits.iter() {
+                for id in wait.wait_connections.iter() {
+                    let to = Connection {
+                        infobase: wait.connection.infobase.clone(),
+                        id: *id,
+                    };
+                    let label = wait
+                        .locks
+                        .iter()
+                        .map(|x| x.space.as_str())
+                        .collect::<Vec<_>>()
+                        .join("\\n");
+                    edges
+                        .entry((node(&wait.connection), node(&to)))
+                        .or_insert((label, "black"));
+                }
+            }
+        }
+        for deadlock in self.deadlocks.iter() {
+            for x in deadlock.intersections.iter() {
+                let connection = |id| Connection {
+                    infobase: deadlock.connection.infobase.clone(),
+                    id,
+                };
+                let label = x
+                    .locks
+                    .iter()
+                    .map(|x| x.space.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\\n");
+                edges.insert(
+                    (node(&connection(x.waiter)), node(&connection(x.holder))),
+                    (label, "red"),
+                );
+            }
+        }
+
+        let mut dot = String::from("digraph locks {\n");
+        for ((from, to), (label, color)) in edges {
+            let _ = writeln!(dot, "  {from} -> {to} [label=\"{label}\", color={color}];");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Waits of every connection sorted by start, with the latest end of the waits up to each one.
+type WaitIndex<'w> = HashMap<&'w Connection, Vec<(&'w Wait, NaiveDateTime)>>;
+
+/// Collects lock events from any number of files in any order and rebuilds wait chains and
+/// deadlocks. Only lock events are kept, so whole directories can be fed to it.
+#[derive(Default)]
+pub struct LockGraph {
+    held: HashMap<Connection, VecDeque<HeldLock>>,
+    waits: Vec<Wait>,
+    deadlocks: Vec<Deadlock>,
+}
+
+impl LockGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, event: &Event) {
+        let Some(decoded) = LockEvent::decode(event) else {
+            return;
+        };
+        let Some(id) = decoded.connect_id else {
+            return;
+        };
+        let connection = Connection {
+            infobase: event
+                .get_prop("p:processName")
+                .map(|x| x.str().into_owned())
+                .unwrap_or_default(),
+            id,
+        };
+        let context = event.get_prop("Context").map(|x| x.str().into_owned());
+
+        if event.name == "TDEADLOCK" {
+            let intersections = event
+                .get_prop("DeadlockConnectionIntersections")
+                .map(|x| parse_intersections(&x.str()))
+                .unwrap_or_default();
+            self.deadlocks.push(Deadlock {
+                date: event.date,
+                connection,
+                intersections,
+                context,
+            });
+        } else if decoded.wait_connections.is_empty() {
+            let held = self.held.entry(connection).or_default();
+            for lock in decoded.locks {
+                if held.len() == HELD_LOCKS_LIMIT {
+                    held.pop_front();
+                }
+                held.push_back(HeldLock {
+                    date: event.date,
+                    lock,
+                    context: context.clone(),
+                });
+            }
+        } else if let Some(start) = event.start() {
+            // a wait with a malformed duration is skipped, its start is unknown
+            self.waits.push(Wait {
+                connection,
+                start,
+                end: event.date,
+                event: event.name.to_string(),
+                wait_connections: decoded.wait_connections,
+                regions: decoded.regions,
+                locks: decoded.locks,
+                context,
+            });
+        }
+    }
+
+    /// Groups the waits by connection and sorts them by start for `overlapping_wait`.
+    fn index_waits(&self) -> WaitIndex<'_> {
+        let mut index = WaitIndex::new();
+        for wait in self.waits.iter() {
+            index
+                .entry(&wait.connection)
+                .or_default()
+                .push((wait, wait.end));
+        }
+        for waits in index.values_mut() {
+            waits.sort_by_key(|(x, _)| x.start);
+            // the latest end so far, so the waits ending before a date are a prefix
+            let mut max_end = NaiveDateTime::MIN;
+            for (x, end) in waits.iter_mut() {
+                max_end = max_end.max(x.end);
+                *end = max_end;
+            }
+        }
+        index
+    }
+
+    /// Finds the wait of `connection` that overlaps `wait` the most.
+    fn overlapping_wait<'w>(
+        index: &WaitIndex<'w>,
+        connection: &Connection,
+        wait: &Wait,
+    ) -> Option<&'w Wait> {
+        let waits = index.get(connection)?;
+        let first = waits.partition_point(|(_, max_end)| *max_end <= wait.start);
+        let last = waits.partition_point(|(x, _)| x.start < wait.end);
+        waits
+            .get(first..last)?
+            .iter()
+            .map(|(x, _)| *x)
+            .filter(|x| wait.start < x.end)
+            .max_by_key(|x| x.end.min(wait.end) - x.start.max(wait.start))
+    }
+
+    /// Finds the latest lock `connection` got before `wait` ended in the spaces `wait` requested.
+    fn blocker_lock(&self, connection: &Connection, wait: &Wait) -> Option<HeldLock> {
+        let held = self.held.get(connection)?;
+        let spaces: Vec<&str> = wait
+            .locks
+            .iter()
+            .map(|x| x.space.as_str())
+            .chain(wait.regions.iter().map(String::as_str))
+            .collect();
+        held.iter()
+            .rev()
+            .filter(|x| x.date <= wait.end)
+            .find(|x| spaces.is_empty() || spaces.contains(&x.lock.space.as_str()))
+            .cloned()
+    }
+
+    fn chain(&self, index: &WaitIndex, wait: &Wait) -> WaitChain {
+        let mut waits = vec![wait.clone()];
+        let mut cycle = false;
+        let mut blocker = None;
+        let mut current = wait;
+        while waits.len() < CHAIN_LIMIT {
+            let mut next = None;
+            for id in current.wait_connections.iter() {
+                let connection = Connection {
+                    infobase: current.connection.infobase.clone(),
+                    id: *id,
+                };
+                if let Some(x) = Self::overlapping_wait(index, &connection, current) {
+                    next = Some(x);
+                    break;
+                }
+                blocker.get_or_insert(connection);
+            }
+            let Some(next) = next else {
+                break;
+            };
+            blocker = None;
+            if waits.iter().any(|x| x.connection == next.connection) {
+                cycle = true;
+                break;
+            }
+            waits.push(next.clone());
+            current = next;
+        }
+
+        let blocker_lock = blocker
+            .as_ref()
+            .and_then(|blocker| self.blocker_lock(blocker, current));
+        WaitChain {
+            waits,
+            blocker,
+            blocker_lock,
+            cycle,
+        }
+    }
+
+    /// Builds a chain for every wait, sorted by the start of the wait.
+    pub fn report(&self) -> LockGraphReport {
+        let index = self.index_waits();
+        let mut chains: Vec<_> = self
+            .waits
+            .iter()
+            .map(|wait| self.chain(&index, wait))
+            .collect();
+        chains.sort_by_key(|x| x.waits[0].start);
+        let mut deadlocks = self.deadlocks.clone();
+        deadlocks.sort_by_key(|x| x.date);
+        LockGraphReport { chains, deadlocks }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+
+    use super::{Connection, LockGraph, Wait, parse_intersections};
+    use crate::{Event, LogStr};
+
+    fn date(sec: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, 4)
+            .and_then(|date| date.and_hms_opt(15, 0, sec))
+            .unwrap()
+    }
+
+    fn add(graph: &mut LockGraph, name: &str, sec: u32, duration: u64, props: &[(&str, &str)]) {
+        let properties: Vec<_> = [("p:processName", "base")]
+            .iter()
+            .chain(props)
+            .map(|(x, y)| (*x, LogStr::new(y.as_bytes(), '\0')))
+            .collect();
+        graph.add(&Event {
+            date: date(sec),
+            duration: Duration::from_secs(duration),
+            name,
+            level: 0,
+            properties: &properties,
+        });
+    }
+
+    #[test]
+    fn test_chain() {
+        let mut graph = LockGraph::new();
+        add(
+            &mut graph,
+            "TLOCK",
+            1,
+            0,
+            &[
+                ("t:connectID", "3"),
+                ("Locks", "InfoRg5.DIMS Exclusive Fld6=1"),
+                ("Context", "Модуль : 1 : Записать();"),
+            ],
+        );
+        add(
+            &mut graph,
+            "TLOCK",
+            20,
+            15,
+            &[
+                ("t:connectID", "1"),
+                ("Locks", "AccumRg7.DIMS Exclusive Fld8=2"),
+                ("WaitConnections", "2"),
+            ],
+        );
+        add(
+            &mut graph,
+            "TTIMEOUT",
+            22,
+            20,
+            &[
+                ("t:connectID", "2"),
+                ("Regions", "InfoRg5.DIMS"),
+                ("Locks", "InfoRg5.DIMS Shared Fld6=1"),
+                ("WaitConnections", "3"),
+            ],
+        );
+        add(&mut graph, "CALL", 23, 1, &[("t:connectID", "3")]);
+
+        let report = graph.report();
+        assert_eq!(report.chains.len(), 2);
+        let chain = &report.chains[1];
+        assert_eq!(chain.waits.len(), 2);
+        assert_eq!(chain.waits[0].connection.id, 1);
+        assert_eq!(chain.waits[1].connection.id, 2);
+        assert!(!chain.cycle);
+        assert_eq!(chain.blocker.as_ref().map(|x| x.id), Some(3));
+        let lock = chain.blocker_lock.as_ref().unwrap();
+        assert_eq!(lock.lock.space, "InfoRg5.DIMS");
+        assert_eq!(lock.context.as_deref(), Some("Модуль : 1 : Записать();"));
+
+        let dot = report.to_dot();
+        assert!(dot.contains("\"base:1\" -> \"base:2\" [label=\"AccumRg7.DIMS\", color=black];"));
+        assert!(dot.contains("\"base:2\" -> \"base:3\""));
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["Chains"][1]["Blocker"]["Id"], 3);
+    }
+
+    #[test]
+    fn test_deadlock() {
+        let intersections = parse_intersections(
+            "24 25 AccumRg17.DIMS Exclusive Fld18=1 Fld19=25,25 24 InfoRg5.DIMS Shared Fld6=\"a b\"",
+        );
+        assert_eq!(intersections.len(), 2);
+        assert_eq!((intersections[0].waiter, intersections[0].holder), (24, 25));
+        assert_eq!(intersections[0].locks[0].fields.len(), 2);
+        assert_eq!((intersections[1].waiter, intersections[1].holder), (25, 24));
+        assert_eq!(intersections[1].locks[0].space, "InfoRg5.DIMS");
+
+        let mut graph = LockGraph::new();
+        add(
+            &mut graph,
+            "TDEADLOCK",
+            5,
+            0,
+            &[
+                ("t:connectID", "24"),
+                (
+                    "DeadlockConnectionIntersections",
+                    "24 25 AccumRg17.DIMS Exclusive Fld18=1,25 24 InfoRg5.DIMS Shared Fld6=2",
+                ),
+            ],
+        );
+        let report = graph.report();
+        assert_eq!(report.deadlocks.len(), 1);
+        assert!(report.to_dot().contains("\"base:25\" -> \"base:24\""));
+    }
+
+    #[test]
+    fn test_oversized_duration() {
+        // the start of the first wait is out of range of chrono, the second one doesn't fit TimeDelta
+        let mut graph = LockGraph::new();
+        for (connection, duration) in [("1", 10_000_000_000_000), ("2", u64::MAX), ("3", 5)] {
+            add(
+                &mut graph,
+                "TLOCK",
+                10,
+                duration,
+                &[("t:connectID", connection), ("WaitConnections", "4")],
+            );
+        }
+        let report = graph.report();
+        assert_eq!(report.chains.len(), 1);
+        assert_eq!(report.chains[0].waits[0].connection.id, 3);
+        assert_eq!(report.chains[0].waits[0].start, date(5));
+    }
+
+    #[test]
+    fn test_many_waits() {
+        // every connection waits for the next one 10 times, the last one waits for nobody
+        let mut graph = LockGraph::new();
+        for id in 0..2000 {
+            for i in 0..10 {
+                let start = date(0) + TimeDelta::seconds(i * 10);
+                graph.waits.push(Wait {
+                    connection: Connection {
+                        infobase: "base".to_string(),
+                        id,
+                    },
+                    start,
+                    end: start + TimeDelta::seconds(5),
+                    event: "TLOCK".to_string(),
+                    wait_connections: vec![id + 1],
+                    regions: Vec::new(),
+                    locks: Vec::new(),
+                    context: None,
+                });
+            }
+        }
+        let report = graph.report();
+        assert_eq!(report.chains.len(), 20000);
+        let chain = |id, start| {
+            report
+                .chains
+                .iter()
+                .find(|x| x.waits[0].connection.id == id && x.waits[0].start == start)
+                .unwrap()
+        };
+        let first = chain(0, date(0));
+        assert_eq!(first.waits.len(), 32);
+        assert!(first.waits.iter().all(|x| x.start == date(0)));
+        let last = chain(1990, date(30));
+        assert_eq!(last.waits.len(), 10);
+        assert!(last.waits.iter().all(|x| x.start == date(30)));
+        assert_eq!(last.blocker.as_ref().map(|x| x.id), Some(2000));
+    }
+}
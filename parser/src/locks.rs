@@ -0,0 +1,226 @@
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::Event;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub enum LockMode {
+    Shared,
+    Exclusive,
+}
+
+impl FromStr for LockMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Shared" => Ok(LockMode::Shared),
+            "Exclusive" => Ok(LockMode::Exclusive),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A managed lock: `AccumRg17.DIMS Exclusive Fld18=12:8b5a00155d4f0a0711e9 Fld19="abc"`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Lock {
+    /// Lock space, e.g. `AccumRg17.DIMS` or `Reference12.REFLOCK`.
+    pub space: String,
+    pub mode: LockMode,
+    /// Field conditions, values are kept as written: `12:8b5a...`, `"abc"`, `T"20240101000000"`.
+    /// Empty for a lock of the whole space.
+    pub fields: Vec<(String, String)>,
+}
+
+/// Splits by whitespace and commas that are not inside double quotes.
+pub(crate) fn lock_tokens(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut in_quotes = false;
+    for (i, ch) in text.char_indices() {
+        if ch == '"' {
+            in_quotes = !in_quotes;
+        }
+        let separator = !in_quotes && (ch == ',' || ch.is_whitespace());
+        match (start, separator) {
+            (Some(s), true) => {
+                tokens.push(&text[s..i]);
+                start = None;
+            }
+            (None, false) => start = Some(i),
+            _ => (),
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(&text[s..]);
+    }
+    tokens
+}
+
+/// Parses locks from tokens produced by `lock_tokens`, a lock starts with a space name followed
+/// by the lock mode, the following `Name=Value` tokens are its fields.
+pub(crate) fn parse_lock_tokens(tokens: &[&str]) -> Vec<Lock> {
+    let mut locks = Vec::<Lock>::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = tokens[i];
+        let mode = tokens.get(i + 1).and_then(|x| x.parse::<LockMode>().ok());
+        match mode {
+            Some(mode) if !token.contains('=') => {
+                locks.push(Lock {
+                    space: token.to_string(),
+                    mode,
+                    fields: Vec::new(),
+                });
+                i += 2;
+                continue;
+            }
+            _ => (),
+        }
+        if let (Some(lock), Some((name, value))) = (locks.last_mut(), token.split_once('=')) {
+            lock.fields.push((name.to_string(), value.to_string()));
+        }
+        i += 1;
+    }
+    locks
+}
+
+/// Parses the `Locks` property of TLOCK and TTIMEOUT events, several locks are separated by commas:
+/// `AccumRg17.DIMS Exclusive Fld18=1 Fld19="a", Reference12.REFLOCK Shared ID=12:8b5a...`.
+pub fn parse_locks(text: &str) -> Vec<Lock> {
+    parse_lock_tokens(&lock_tokens(text))
+}
+
+/// Parses the `Regions` property: `AccumRg17.DIMS,Reference12.REFLOCK`.
+pub fn parse_regions(text: &str) -> Vec<String> {
+    lock_tokens(text).into_iter().map(str::to_string).collect()
+}
+
+/// Parses a list of connection numbers like `WaitConnections=12,15`.
+pub fn parse_connections(text: &str) -> Vec<u64> {
+    lock_tokens(text)
+        .into_iter()
+        .filter_map(|x| x.parse().ok())
+        .collect()
+}
+
+/// Lock related properties of TLOCK, TTIMEOUT and TDEADLOCK events.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LockEvent {
+    /// Connection number from `t:connectID`.
+    pub connect_id: Option<u64>,
+    pub regions: Vec<String>,
+    pub locks: Vec<Lock>,
+    /// Connections the event waited for, from `WaitConnections`.
+    pub wait_connections: Vec<u64>,
+}
+
+impl LockEvent {
+    pub const EVENT_NAMES: [&str; 3] = ["TLOCK", "TTIMEOUT", "TDEADLOCK"];
+
+    /// Returns `None` if the event is not a lock event.
+    pub fn decode(event: &Event) -> Option<LockEvent> {
+        if !Self::EVENT_NAMES.contains(&event.name) {
+            return None;
+        }
+        let prop = |name| event.get_prop(name).map(|x| x.str());
+        Some(LockEvent {
+            connect_id: prop("t:connectID").and_then(|x| x.parse().ok()),
+            regions: prop("Regions")
+                .map(|x| parse_regions(&x))
+                .unwrap_or_default(),
+            locks: prop("Locks").map(|x| parse_locks(&x)).unwrap_or_default(),
+            wait_connections: prop("WaitConnections")
+                .map(|x| parse_connections(&x))
+                .unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+
+    use super::{Lock, LockEvent, LockMode, parse_locks, parse_regions};
+    use crate::{Event, LogStr};
+
+    fn lock(space: &str, mode: LockMode, fields: &[(&str, &str)]) -> Lock {
+        Lock {
+            space: space.to_string(),
+            mode,
+            fields: fields
+                .iter()
+                .map(|(x, y)| (x.to_string(), y.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_locks() {
+        let locks = parse_locks(
+            "AccumRg17.DIMS Exclusive Fld18=12:8b5a00155d4f0a07, Fld19=\"a, b\" Period=T\"20240101000000\",\
+            Reference12.REFLOCK Shared ID=12:9c6b00155d4f0a07,InfoRg5.DIMS Exclusive",
+        );
+        assert_eq!(
+            locks,
+            [
+                lock(
+                    "AccumRg17.DIMS",
+                    LockMode::Exclusive,
+                    &[
+                        ("Fld18", "12:8b5a00155d4f0a07"),
+                        ("Fld19", "\"a, b\""),
+                        ("Period", "T\"20240101000000\""),
+                    ]
+                ),
+                lock(
+                    "Reference12.REFLOCK",
+                    LockMode::Shared,
+                    &[("ID", "12:9c6b00155d4f0a07")]
+                ),
+                lock("InfoRg5.DIMS", LockMode::Exclusive, &[]),
+            ]
+        );
+        assert!(parse_locks("").is_empty());
+        assert_eq!(
+            parse_regions("AccumRg17.DIMS,Reference12.REFLOCK"),
+            ["AccumRg17.DIMS", "Reference12.REFLOCK"]
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        let properties = [
+            ("process", LogStr::new(b"rphost", '\0')),
+            ("t:connectID", LogStr::new(b"42", '\0')),
+            ("Regions", LogStr::new(b"InfoRg5.DIMS", '\0')),
+            ("Locks", LogStr::new(b"InfoRg5.DIMS Exclusive Fld6=1", '\0')),
+            ("WaitConnections", LogStr::new(b"7,8", '\0')),
+        ];
+        let mut event = Event {
+            date: NaiveDate::from_ymd_opt(2024, 1, 4)
+                .and_then(|date| date.and_hms_opt(15, 0, 0))
+                .unwrap(),
+            duration: Duration::from_micros(1),
+            name: "TLOCK",
+            level: 0,
+            properties: &properties,
+        };
+        let decoded = LockEvent::decode(&event).unwrap();
+        assert_eq!(decoded.connect_id, Some(42));
+        assert_eq!(decoded.regions, ["InfoRg5.DIMS"]);
+        assert_eq!(
+            decoded.locks,
+            [lock("InfoRg5.DIMS", LockMode::Exclusive, &[("Fld6", "1")])]
+        );
+        assert_eq!(decoded.wait_connections, [7, 8]);
+
+        event.name = "CALL";
+        assert!(LockEvent::decode(&event).is_none());
+    }
+}
@@ -1,5 +1,3 @@
-use std::marker::PhantomData;
-
 use crate::types::LogStr;
 
 #[derive(Debug, PartialEq)]
@@ -8,7 +6,8 @@ pub struct EndError;
 #[derive(Debug, PartialEq)]
 pub enum ParseError {
     End,
-    InvalidFormat,
+    /// The record is malformed, the value is the byte offset of the error in the buffer.
+    InvalidFormat(usize),
 }
 
 impl From<EndError> for ParseError {
@@ -19,95 +18,109 @@ impl From<EndError> for ParseError {
 
 pub type ParseResult<T> = std::result::Result<T, ParseError>;
 
+pub trait Number: Copy + Default {
+    fn push_digit(self, digit: u8) -> Option<Self>;
+}
+
+macro_rules! impl_number {
+    ($($t:ty),*) => {
+        $(
+            impl Number for $t {
+                #[inline]
+                fn push_digit(self, digit: u8) -> Option<Self> {
+                    self.checked_mul(10)?.checked_add(digit as $t)
+                }
+            }
+        )*
+    };
+}
+
+impl_number!(u8, u16, u32, u64, u128, usize);
+
 pub struct Parser<'a> {
-    source: *const u8,
-    ptr: *const u8,
-    end: *const u8,
+    buffer: &'a [u8],
+    pos: usize,
     pub(crate) prop_buf: Vec<(&'a str, LogStr<'a>)>,
-    _marker: PhantomData<&'a u8>,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(buffer: &'a [u8]) -> Parser<'a> {
-        let ptr = buffer.as_ptr();
-        let end = unsafe { ptr.add(buffer.len()) };
         Parser {
-            source: ptr,
-            ptr,
-            end,
+            buffer,
+            pos: 0,
             prop_buf: Vec::new(),
-            _marker: PhantomData,
         }
     }
 
     pub fn position(&self) -> usize {
-        unsafe { self.ptr.offset_from(self.source) as usize }
+        self.pos
+    }
+
+    #[inline]
+    fn rest(&self) -> &'a [u8] {
+        &self.buffer[self.pos..]
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Result<u8, EndError> {
-        if self.ptr == self.end {
-            Err(EndError)
-        } else {
-            let v = unsafe { *self.ptr };
-            self.ptr = unsafe { self.ptr.add(1) };
-            Ok(v)
-        }
+        let v = self.peek()?;
+        self.pos += 1;
+        Ok(v)
     }
 
     pub fn skip(&mut self, count: usize) -> Result<(), EndError> {
-        let new_ptr = unsafe { self.ptr.add(count) };
-        if new_ptr > self.end {
+        if count > self.buffer.len() - self.pos {
             Err(EndError)
         } else {
-            self.ptr = new_ptr;
+            self.pos += count;
             Ok(())
         }
     }
 
     pub fn skip_to(&mut self, ch: u8) -> Result<(), EndError> {
-        let len = unsafe { self.end.offset_from(self.ptr) } as usize;
-        let haystack = unsafe { std::slice::from_raw_parts(self.ptr, len) };
-        let i = memchr::memchr(ch, haystack).ok_or(EndError)?;
-        self.skip(i + 1)
+        let i = memchr::memchr(ch, self.rest()).ok_or(EndError)?;
+        self.pos += i + 1;
+        Ok(())
     }
 
     pub fn skip_to2(&mut self, ch1: u8, ch2: u8) -> Result<(), EndError> {
-        let len = unsafe { self.end.offset_from(self.ptr) } as usize;
-        let haystack = unsafe { std::slice::from_raw_parts(self.ptr, len) };
-        let i = memchr::memchr2(ch1, ch2, haystack).ok_or(EndError)?;
-        self.skip(i + 1)
+        let i = memchr::memchr2(ch1, ch2, self.rest()).ok_or(EndError)?;
+        self.pos += i + 1;
+        Ok(())
     }
 
     pub fn peek(&self) -> Result<u8, EndError> {
-        if self.ptr == self.end {
-            Err(EndError)
-        } else {
-            let v = unsafe { *self.ptr };
-            Ok(v)
-        }
+        self.buffer.get(self.pos).copied().ok_or(EndError)
     }
 
-    pub fn parse_number<T>(&mut self, delimiter: char) -> ParseResult<T>
-    where
-        T: Default + std::ops::Mul<T, Output = T> + std::ops::Add<T, Output = T> + From<u8>,
-    {
-        let mut number: T = T::default();
+    pub fn parse_number<T: Number>(&mut self, delimiter: char) -> ParseResult<T> {
+        let mut number = T::default();
+        let mut digits = 0usize;
         loop {
+            let position = self.position();
             let next = self.next()?;
-            if next == delimiter as _ {
+            if next == delimiter as u8 {
+                if digits == 0 {
+                    return Err(ParseError::InvalidFormat(position));
+                }
                 break;
             }
-            number = number * T::from(10) + T::from(next - b'0');
+            if !next.is_ascii_digit() {
+                return Err(ParseError::InvalidFormat(position));
+            }
+            number = number
+                .push_digit(next - b'0')
+                .ok_or(ParseError::InvalidFormat(position))?;
+            digits += 1;
         }
         Ok(number)
     }
 
     pub fn parse_name(&mut self, delimiter: char) -> ParseResult<&'a str> {
-        let ptr = self.ptr;
+        let start = self.pos;
         self.skip_to(delimiter as _)?;
-        let slice =
-            unsafe { std::slice::from_raw_parts(ptr, self.ptr.offset_from(ptr) as usize - 1) };
-        std::str::from_utf8(slice).map_err(|_| ParseError::InvalidFormat)
+        let slice = &self.buffer[start..self.pos - 1];
+        std::str::from_utf8(slice).map_err(|_| ParseError::InvalidFormat(start))
     }
 
     pub fn parse_value(&mut self) -> ParseResult<LogStr<'a>> {
@@ -120,16 +133,14 @@ impl<'a> Parser<'a> {
     }
 
     pub fn parse_str(&mut self) -> ParseResult<&'a [u8]> {
-        let ptr = self.ptr;
+        let start = self.pos;
         self.skip_to2(b',', b'\r')?;
-        let slice =
-            unsafe { std::slice::from_raw_parts(ptr, self.ptr.offset_from(ptr) as usize - 1) };
-        Ok(slice)
+        Ok(&self.buffer[start..self.pos - 1])
     }
 
     pub fn parse_str_quote(&mut self, quote: char) -> ParseResult<LogStr<'a>> {
         self.skip(1)?;
-        let ptr = self.ptr;
+        let start = self.pos;
         let mut need_replace_quotes = false;
 
         loop {
@@ -137,12 +148,12 @@ impl<'a> Parser<'a> {
             let next = self.next()?;
             if next == b',' || next == b'\r' {
                 break;
-            } else if next == quote as _ {
+            } else if next == quote as u8 {
                 need_replace_quotes = true;
             }
         }
 
-        let s = unsafe { std::slice::from_raw_parts(ptr, self.ptr.offset_from(ptr) as usize - 2) };
+        let s = &self.buffer[start..self.pos - 2];
         Ok(LogStr::new(
             s,
             if need_replace_quotes {
@@ -156,7 +167,9 @@ impl<'a> Parser<'a> {
 
 #[cfg(test)]
 mod tests {
-    use crate::parser::ParseResult;
+    use chrono::NaiveDate;
+
+    use crate::parser::{ParseError, ParseResult};
 
     use super::Parser;
 
@@ -253,4 +266,52 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_number_invalid_digit() {
+        let mut parser = Parser::new(b"5x:20");
+        assert_eq!(
+            parser.parse_number::<u32>(':'),
+            Err(ParseError::InvalidFormat(1))
+        );
+    }
+
+    #[test]
+    fn test_number_empty() {
+        let mut parser = Parser::new(b":20");
+        assert_eq!(
+            parser.parse_number::<u32>(':'),
+            Err(ParseError::InvalidFormat(0))
+        );
+    }
+
+    #[test]
+    fn test_number_overflow() -> ParseResult<()> {
+        let mut parser = Parser::new(b"4294967296,4294967295,");
+        assert_eq!(
+            parser.parse_number::<u32>(','),
+            Err(ParseError::InvalidFormat(9))
+        );
+        parser.skip_to(b',')?;
+        assert_eq!(parser.parse_number::<u32>(',')?, u32::MAX);
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_invalid_time() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 4)
+            .and_then(|date| date.and_hms_opt(15, 0, 0))
+            .unwrap();
+        for buf in [
+            &b"60:20.886000-1,EXCP,0,process=rphost\r\n"[..],
+            &b"57:60.886000-1,EXCP,0,process=rphost\r\n"[..],
+            &b"57:20.1000000-1,EXCP,0,process=rphost\r\n"[..],
+        ] {
+            let mut parser = Parser::new(buf);
+            assert!(matches!(
+                crate::parse_record(&mut parser, date),
+                Err(ParseError::InvalidFormat(0))
+            ));
+        }
+    }
 }
@@ -0,0 +1,627 @@
+//! A textual form of filters:
+//!
+//! ```text
+//! name in (DBMSSQL, SDBL) and duration >= 100ms and Sql ~ "^select" and not Usr = "Robot"
+//! ```
+//!
+//! A condition is `field op value`, `field in (value, ...)` or `property exists`, conditions are
+//! combined with `and`, `or`, `not` and parentheses. The fields are `name`, `level`, `duration`,
+//! `date`, `time` (time of day), any other word is a property name. Operators:
+//! - `=`, `!=`, `~` (regular expression, as `Match`), `!~`, `contains`, `startswith`, `endswith`,
+//!   `in` for the name and properties;
+//! - `<`, `<=`, `>`, `>=` for properties compare their values as numbers: `Rows > 10000`,
+//!   so do `=` and `!=` with an unquoted number: `Rows = 1e4`, a quoted one is compared as
+//!   a string: `Rows = "10000"`;
+//! - `=`, `!=`, `<`, `<=`, `>`, `>=` for the level, the duration, the date and the time.
+//!
+//! Values are words or strings in double or single quotes, a quote inside is doubled. A duration
+//! is a number with an optional unit: `us` (default), `ms`, `s`, `min`. A date is written as
+//! `2024-01-04T15:00:00`, `"2024-01-04 15:00:00.123"` or `2024-01-04`, a time as `09:30` or
+//! `09:30:00.5`.
+
+use std::{error::Error, fmt};
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::filters::{
+    DataFilter, DurationFilter, Filter, Match, NumFilter, PropFilter, PropNumFilter, StrFilter,
+    TimeFilter,
+};
+
+#[derive(Debug, PartialEq)]
+pub struct QueryError {
+    /// Position of the error in characters, starting from 1.
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl Error for QueryError {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Punct(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(x) => write!(f, "`{x}`"),
+            Token::Str(x) => write!(f, "string \"{x}\""),
+            Token::Punct(x) => write!(f, "`{x}`"),
+        }
+    }
+}
+
+const RANGE_OPS: [&str; 6] = [">=", "<=", ">", "<", "=", "!="];
+
+const WORD_OPS: [&str; 5] = ["in", "contains", "startswith", "endswith", "exists"];
+
+const PUNCTS: [&str; 11] = ["!=", "!~", ">=", "<=", "(", ")", ",", "=", "~", ">", "<"];
+
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>, QueryError> {
+    let error = |pos: usize, message: &str| QueryError {
+        position: text[..pos].chars().count() + 1,
+        message: message.to_string(),
+    };
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < text.len() {
+        let rest = &text[pos..];
+        let ch = rest.chars().next().unwrap_or_default();
+        if ch.is_whitespace() {
+            pos += ch.len_utf8();
+        } else if ch == '"' || ch == '\'' {
+            let mut value = String::new();
+            let mut end = None;
+            let mut chars = rest.char_indices().skip(1).peekable();
+            while let Some((i, x)) = chars.next() {
+                if x == ch {
+                    if chars.peek().is_some_and(|(_, y)| *y == ch) {
+                        chars.next();
+                    } else {
+                        end = Some(i + 1);
+                        break;
+                    }
+                }
+                value.push(x);
+            }
+            let Some(end) = end else {
+                return Err(error(pos, "unterminated string"));
+            };
+            tokens.push((pos, Token::Str(value)));
+            pos += end;
+        } else if let Some(punct) = PUNCTS.iter().find(|x| rest.starts_with(**x)) {
+            tokens.push((pos, Token::Punct(punct)));
+            pos += punct.len();
+        } else if ch == '!' {
+            return Err(error(pos, "unexpected `!`, expected `!=` or `!~`"));
+        } else {
+            let len = rest
+                .find(|x: char| x.is_whitespace() || "\"'()!,=~<>".contains(x))
+                .unwrap_or(rest.len());
+            tokens.push((pos, Token::Word(rest[..len].to_string())));
+            pos += len;
+        }
+    }
+    Ok(tokens)
+}
+
+enum Field {
+    Name,
+    Level,
+    Duration,
+    Date,
+    Time,
+    Prop(String),
+}
+
+/// Comparisons every range filter has.
+enum Cmp {
+    GreaterOrEqual,
+    LessOrEqual,
+    Greater,
+    Less,
+}
+
+struct QueryParser<'a> {
+    text: &'a str,
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+}
+
+impl QueryParser<'_> {
+    fn error_at(&self, index: usize, message: String) -> QueryError {
+        let pos = self.tokens.get(index).map_or(self.text.len(), |x| x.0);
+        QueryError {
+            position: self.text[..pos].chars().count() + 1,
+            message,
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> QueryError {
+        let found = match self.tokens.get(self.pos) {
+            Some((_, token)) => token.to_string(),
+            None => "end of query".to_string(),
+        };
+        self.error_at(self.pos, format!("expected {expected}, found {found}"))
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|x| &x.1)
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(x)) if x.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn punct(&mut self, punct: &str) -> bool {
+        match self.peek() {
+            Some(Token::Punct(x)) if *x == punct => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn or(&mut self) -> Result<Filter, QueryError> {
+        let mut items = vec![self.and()?];
+        while self.keyword("or") {
+            items.push(self.and()?);
+        }
+        Ok(if items.len() == 1 {
+            items.pop().unwrap()
+        } else {
+            Filter::Or(items)
+        })
+    }
+
+    fn and(&mut self) -> Result<Filter, QueryError> {
+        let mut items = vec![self.unary()?];
+        while self.keyword("and") {
+            items.push(self.unary()?);
+        }
+        Ok(if items.len() == 1 {
+            items.pop().unwrap()
+        } else {
+            Filter::And(items)
+        })
+    }
+
+    fn unary(&mut self) -> Result<Filter, QueryError> {
+        if self.keyword("not") {
+            return Ok(Filter::Not(Box::new(self.unary()?)));
+        }
+        if self.punct("(") {
+            let condition = self.or()?;
+            if !self.punct(")") {
+                return Err(self.unexpected("`)`"));
+            }
+            return Ok(condition);
+        }
+        self.comparison()
+    }
+
+    /// Returns the value and the index of its token.
+    fn value(&mut self) -> Result<(String, usize), QueryError> {
+        match self.peek() {
+            Some(Token::Word(x) | Token::Str(x)) => {
+                let value = x.clone();
+                self.pos += 1;
+                Ok((value, self.pos - 1))
+            }
+            _ => Err(self.unexpected("a value")),
+        }
+    }
+
+    fn list(&mut self) -> Result<Vec<String>, QueryError> {
+        if !self.punct("(") {
+            return Err(self.unexpected("`(`"));
+        }
+        let mut values = vec![self.value()?.0];
+        while self.punct(",") {
+            values.push(self.value()?.0);
+        }
+        if !self.punct(")") {
+            return Err(self.unexpected("`,` or `)`"));
+        }
+        Ok(values)
+    }
+
+    fn comparison(&mut self) -> Result<Filter, QueryError> {
+        let field = match self.peek() {
+            Some(Token::Word(x))
+                if ["and", "or", "not", "in"]
+                    .iter()
+                    .any(|y| x.eq_ignore_ascii_case(y)) =>
+            {
+                return Err(self.unexpected("a field"));
+            }
+            Some(Token::Word(x)) if x.eq_ignore_ascii_case("name") => Field::Name,
+            Some(Token::Word(x)) if x.eq_ignore_ascii_case("level") => Field::Level,
+            Some(Token::Word(x)) if x.eq_ignore_ascii_case("duration") => Field::Duration,
+            Some(Token::Word(x)) if x.eq_ignore_ascii_case("date") => Field::Date,
+            Some(Token::Word(x)) if x.eq_ignore_ascii_case("time") => Field::Time,
+            Some(Token::Word(x) | Token::Str(x)) => Field::Prop(x.clone()),
+            _ => return Err(self.unexpected("a field")),
+        };
+        self.pos += 1;
+
+        let op_index = self.pos;
+        let op = match self.peek() {
+            Some(Token::Punct(x)) if !matches!(*x, "(" | ")" | ",") => *x,
+            Some(Token::Word(x)) => match WORD_OPS.iter().find(|y| x.eq_ignore_ascii_case(y)) {
+                Some(op) => *op,
+                None => return Err(self.unexpected("an operator")),
+            },
+            _ => return Err(self.unexpected("an operator")),
+        };
+        self.pos += 1;
+
+        let unsupported = || {
+            let field = &self.tokens[op_index - 1].1;
+            Err(self.error_at(
+                op_index,
+                format!("operator `{op}` is not supported for {field}"),
+            ))
+        };
+        match field {
+            Field::Name if op == "exists" => unsupported(),
+            Field::Name => self.str_filter(op, op_index, Filter::Name),
+            Field::Prop(name) if op == "exists" => Ok(Filter::HasProp(name)),
+            Field::Prop(name) if matches!(op, "<" | "<=" | ">" | ">=") => {
+                let value = self.number()?;
+                Ok(Self::range(op, |cmp| {
+                    Filter::PropNum(PropNumFilter {
+                        name: name.clone(),
+                        filter: num_filter(cmp, value),
+                    })
+                }))
+            }
+            Field::Prop(name) if matches!(op, "=" | "!=") && self.peek_number().is_some() => {
+                let value = self.number()?;
+                let filter = Filter::PropNum(PropNumFilter {
+                    name,
+                    filter: NumFilter::Equal(value),
+                });
+                Ok(match op {
+                    "=" => filter,
+                    _ => Filter::Not(Box::new(filter)),
+                })
+            }
+            Field::Prop(name) => self.str_filter(op, op_index, |filter| {
+                Filter::Prop(PropFilter {
+                    name: name.clone(),
+                    filter,
+                })
+            }),
+            _ if !RANGE_OPS.contains(&op) => unsupported(),
+            Field::Level => {
+                let value = self.number()?;
+                Ok(Self::range(op, |cmp| Filter::Level(num_filter(cmp, value))))
+            }
+            Field::Duration => {
+                let (value, index) = self.value()?;
+                let value = parse_duration(&value)
+                    .ok_or_else(|| self.error_at(index, format!("invalid duration `{value}`")))?;
+                Ok(Self::range(op, |cmp| {
+                    Filter::Duration(match cmp {
+                        Cmp::GreaterOrEqual => DurationFilter::GreaterOrEqual(value),
+                        Cmp::LessOrEqual => DurationFilter::LessOrEqual(value),
+                        Cmp::Greater => DurationFilter::Greater(value),
+                        Cmp::Less => DurationFilter::Less(value),
+                    })
+                }))
+            }
+            Field::Date => {
+                let (value, index) = self.value()?;
+                let value = parse_date(&value)
+                    .ok_or_else(|| self.error_at(index, format!("invalid date `{value}`")))?;
+                Ok(Self::range(op, |cmp| {
+                    Filter::Date(match cmp {
+                        Cmp::GreaterOrEqual => DataFilter::GreaterOrEqual(value),
+                        Cmp::LessOrEqual => DataFilter::LessOrEqual(value),
+                        Cmp::Greater => DataFilter::Greater(value),
+                        Cmp::Less => DataFilter::Less(value),
+                    })
+                }))
+            }
+            Field::Time => {
+                let (value, index) = self.value()?;
+                let value = parse_time(&value)
+                    .ok_or_else(|| self.error_at(index, format!("invalid time `{value}`")))?;
+                Ok(Self::range(op, |cmp| {
+                    Filter::Time(match cmp {
+                        Cmp::GreaterOrEqual => TimeFilter::GreaterOrEqual(value),
+                        Cmp::LessOrEqual => TimeFilter::LessOrEqual(value),
+                        Cmp::Greater => TimeFilter::Greater(value),
+                        Cmp::Less => TimeFilter::Less(value),
+                    })
+                }))
+            }
+        }
+    }
+
+    /// The next token is an unquoted finite number.
+    fn peek_number(&self) -> Option<f64> {
+        match self.peek() {
+            Some(Token::Word(x)) => x.parse().ok().filter(|x: &f64| x.is_finite()),
+            _ => None,
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, QueryError> {
+        let (value, index) = self.value()?;
+        value
+            .parse()
+            .map_err(|_| self.error_at(index, format!("invalid number `{value}`")))
+    }
+
+    /// `op` is one of `RANGE_OPS`, `=` and `!=` are expressed with `GreaterOrEqual` and
+    /// `LessOrEqual`.
+    fn range(op: &str, filter: impl Fn(Cmp) -> Filter) -> Filter {
+        let between = || Filter::And(vec![filter(Cmp::GreaterOrEqual), filter(Cmp::LessOrEqual)]);
+        match op {
+            ">=" => filter(Cmp::GreaterOrEqual),
+            "<=" => filter(Cmp::LessOrEqual),
+            ">" => filter(Cmp::Greater),
+            "<" => filter(Cmp::Less),
+            "=" => between(),
+            _ => Filter::Not(Box::new(between())),
+        }
+    }
+
+    /// `filter` makes a filter of the field from a string filter.
+    fn str_filter(
+        &mut self,
+        op: &str,
+        op_index: usize,
+        filter: impl Fn(StrFilter) -> Filter,
+    ) -> Result<Filter, QueryError> {
+        let not = |x| Filter::Not(Box::new(x));
+        if op == "in" {
+            return Ok(filter(StrFilter::InList(self.list()?)));
+        }
+        let (value, index) = self.value()?;
+        let regex = |value: &str| {
+            Match::new(value).map_err(|e| self.error_at(index, format!("invalid regex: {e}")))
+        };
+        Ok(match op {
+            "=" => filter(StrFilter::Equal(value)),
+            "!=" => not(filter(StrFilter::Equal(value))),
+            "~" => filter(StrFilter::Match(regex(&value)?)),
+            "!~" => not(filter(StrFilter::Match(regex(&value)?))),
+            "contains" => filter(StrFilter::Contains(value)),
+            "startswith" => filter(StrFilter::StartsWith(value)),
+            "endswith" => filter(StrFilter::EndsWith(value)),
+            _ => {
+                return Err(self.error_at(
+                    op_index,
+                    format!("operator `{op}` is not supported for strings"),
+                ));
+            }
+        })
+    }
+}
+
+fn parse_duration(value: &str) -> Option<u128> {
+    let len = value
+        .find(|x: char| !x.is_ascii_digit())
+        .unwrap_or(value.len());
+    let number: u128 = value[..len].parse().ok()?;
+    let multiplier = match &value[len..] {
+        "" | "us" => 1,
+        "ms" => 1_000,
+        "s" => 1_000_000,
+        "min" => 60_000_000,
+        _ => return None,
+    };
+    number.checked_mul(multiplier)
+}
+
+fn num_filter(cmp: Cmp, value: f64) -> NumFilter {
+    match cmp {
+        Cmp::GreaterOrEqual => NumFilter::GreaterOrEqual(value),
+        Cmp::LessOrEqual => NumFilter::LessOrEqual(value),
+        Cmp::Greater => NumFilter::Greater(value),
+        Cmp::Less => NumFilter::Less(value),
+    }
+}
+
+fn parse_time(value: &str) -> Option<NaiveTime> {
+    ["%H:%M:%S%.f", "%H:%M"]
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(value, format).ok())
+}
+
+/// Parses a date as in queries: `2024-01-04T15:00:00`, `2024-01-04 15:00:00.5` or `2024-01-04`.
+pub fn parse_date(value: &str) -> Option<NaiveDateTime> {
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|x| x.and_hms_opt(0, 0, 0))
+        })
+}
+
+/// Parses a query into a filter.
+pub fn parse(text: &str) -> Result<Filter, QueryError> {
+    let mut parser = QueryParser {
+        text,
+        tokens: tokenize(text)?,
+        pos: 0,
+    };
+    let condition = parser.or()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(parser.unexpected("`and`, `or` or end of query"));
+    }
+    Ok(condition)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+
+    use super::{QueryError, parse};
+    use crate::{Event, LogStr};
+
+    fn check(query: &str, name: &str, duration: u64, props: &[(&str, &str)]) -> bool {
+        let properties: Vec<_> = props
+            .iter()
+            .map(|(name, value)| (*name, LogStr::new(value.as_bytes(), '\0')))
+            .collect();
+        let event = Event {
+            date: NaiveDate::from_ymd_opt(2024, 1, 4)
+                .and_then(|date| date.and_hms_opt(15, 0, 0))
+                .unwrap(),
+            duration: Duration::from_micros(duration),
+            name,
+            level: 2,
+            properties: &properties,
+        };
+        parse(query).unwrap().check(&event)
+    }
+
+    #[test]
+    fn test_query() {
+        let query = r#"name in (DBMSSQL, SDBL) and duration >= 100ms and Sql ~ "^select" and not Usr = "Robot""#;
+        let props = [("Usr", "Admin"), ("Sql", "SELECT 1")];
+        assert!(check(query, "DBMSSQL", 100_000, &props));
+        assert!(!check(query, "DBMSSQL", 99_999, &props));
+        assert!(!check(query, "CALL", 100_000, &props));
+        assert!(!check(
+            query,
+            "SDBL",
+            100_000,
+            &[("Usr", "Robot"), ("Sql", "select")]
+        ));
+
+        assert!(check(
+            "name = CALL or duration > 1s",
+            "EXCP",
+            1_000_001,
+            &[]
+        ));
+        assert!(!check(
+            "name = CALL or (duration > 1s)",
+            "EXCP",
+            1_000_000,
+            &[]
+        ));
+        assert!(check("duration = 5", "CALL", 5, &[]));
+        assert!(check(
+            "NOT Usr != 'O''Neil'",
+            "CALL",
+            5,
+            &[("usr", "O'Neil")]
+        ));
+        assert!(check(
+            "Descr contains fail",
+            "EXCP",
+            5,
+            &[("Descr", "it failed")]
+        ));
+        assert!(check("Descr !~ fail", "EXCP", 5, &[]));
+        assert!(check(
+            "date >= 2024-01-04 and date < '2024-01-04 15:00:00.1'",
+            "CALL",
+            5,
+            &[]
+        ));
+        assert!(!check("date > 2024-01-04T15:00:00", "CALL", 5, &[]));
+
+        assert!(check("level >= 2 and level != 3", "CALL", 5, &[]));
+        assert!(check("time >= 09:00 and time < '18:00:00'", "CALL", 5, &[]));
+        assert!(!check("time > 15:00", "CALL", 5, &[]));
+        let props = [("Rows", "10001"), ("MemoryPeak", "1000000000")];
+        assert!(check(
+            "Rows > 10000 and MemoryPeak >= 1e9",
+            "CALL",
+            5,
+            &props
+        ));
+        assert!(!check("Rows > 10001 or Rows != 10001.0", "CALL", 5, &props));
+        assert!(check(
+            "Rows = 10001.0 and Rows = 1.0001e4 and Rows >= 10001.0 and Rows <= 10001.0",
+            "CALL",
+            5,
+            &props
+        ));
+        assert!(check(
+            "Rows = '10001' and Rows != '10001.0' and MemoryPeak != 1",
+            "CALL",
+            5,
+            &props
+        ));
+        assert!(check("rows exists and not Usr exists", "CALL", 5, &props));
+        assert!(check(
+            "name startswith DB and name endswith SQL",
+            "DBMSSQL",
+            5,
+            &[]
+        ));
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |query| parse(query).err().unwrap();
+        let at = |position, message: &str| QueryError {
+            position,
+            message: message.to_string(),
+        };
+        assert_eq!(
+            error("name = CALL and"),
+            at(16, "expected a field, found end of query")
+        );
+        assert_eq!(
+            error("duration >= 10 hours"),
+            at(16, "expected `and`, `or` or end of query, found `hours`")
+        );
+        assert_eq!(error("duration >= 10h"), at(13, "invalid duration `10h`"));
+        assert_eq!(
+            error("Имя ~ \"(\""),
+            at(
+                7,
+                "invalid regex: regex parse error:\n    (\n    ^\nerror: unclosed group"
+            )
+        );
+        assert_eq!(
+            error("(name = CALL"),
+            at(13, "expected `)`, found end of query")
+        );
+        assert_eq!(error("name = 'CALL"), at(8, "unterminated string"));
+        assert_eq!(
+            error("name > CALL"),
+            at(6, "operator `>` is not supported for strings")
+        );
+        assert_eq!(
+            error("date contains 1"),
+            at(6, "operator `contains` is not supported for `date`")
+        );
+        assert_eq!(error("name in CALL"), at(9, "expected `(`, found `CALL`"));
+        assert_eq!(error("Rows >= many"), at(9, "invalid number `many`"));
+        assert_eq!(error("time > 25:00"), at(8, "invalid time `25:00`"));
+        assert_eq!(
+            error("name exists"),
+            at(6, "operator `exists` is not supported for `name`")
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use std::{error::Error, fs::File, io::Read, path::Path};
+
+use chrono::NaiveDateTime;
+
+use crate::{OwnedEvent, ParseError, Parser, io::open_file, parse_date_file, parse_record};
+
+/// Initial size of the read buffer, it grows for longer records.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Reads events one by one, unlike `parse_file` the caller decides when to read the next event,
+/// e.g. to merge several files by date. Only the read buffer is kept in memory.
+pub struct EventReader<R: Read> {
+    inner: R,
+    date: NaiveDateTime,
+    buffer: Vec<u8>,
+    start: usize,
+    end: usize,
+    eof: bool,
+}
+
+impl EventReader<File> {
+    /// Opens a log file named `yymmddhh.log`.
+    pub fn open(file_name: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let date = parse_date_file(&file_name).ok_or("invalid file name")?;
+        Ok(Self::new(open_file(file_name)?, date))
+    }
+}
+
+impl<R: Read> EventReader<R> {
+    /// `date` is the hour of the events, see `parse_date_file`.
+    pub fn new(inner: R, date: NaiveDateTime) -> Self {
+        Self {
+            inner,
+            date,
+            buffer: vec![0; BUFFER_SIZE],
+            start: 0,
+            end: 0,
+            eof: false,
+        }
+    }
+
+    /// Returns `None` at the end, an incomplete record at the end is skipped as by `parse_file`.
+    pub fn next_event(&mut self) -> Result<Option<OwnedEvent>, Box<dyn Error>> {
+        loop {
+            let rest = &self.buffer[self.start..self.end];
+            let mut parser = Parser::new(rest);
+            match parse_record(&mut parser, self.date) {
+                Ok(event) => {
+                    let event = event.to_owned_event();
+                    self.start += parser.position();
+                    return Ok(Some(event));
+                }
+                Err(ParseError::InvalidFormat(_)) => {
+                    if let Some(i) = memchr::memchr(b'\n', rest) {
+                        self.start += i + 1;
+                        continue;
+                    }
+                }
+                Err(ParseError::End) => (),
+            }
+            if self.eof {
+                return Ok(None);
+            }
+            self.fill()?;
+        }
+    }
+
+    /// Reads more data after the unparsed rest, the buffer grows if the rest fills it.
+    fn fill(&mut self) -> Result<(), Box<dyn Error>> {
+        self.buffer.copy_within(self.start..self.end, 0);
+        self.end -= self.start;
+        self.start = 0;
+        if self.end == self.buffer.len() {
+            self.buffer.resize(self.buffer.len() * 2, 0);
+        }
+        let len = self.inner.read(&mut self.buffer[self.end..])?;
+        self.end += len;
+        self.eof = len == 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::{BUFFER_SIZE, EventReader};
+
+    #[test]
+    fn test_reader() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 4)
+            .and_then(|date| date.and_hms_opt(15, 0, 0))
+            .unwrap();
+        let long = "x".repeat(BUFFER_SIZE * 3);
+        let log = format!(
+            "57:20.886000-1,EXCP,0,process=rphost\r\n\
+            xx:20.886000-1,EXCP,0,process=rphost\r\n\
+            57:21.000000-2,CALL,1,Descr={long}\r\n\
+            57:22.000000-3,CALL,1,Usr='incomplete"
+        );
+        let mut reader = EventReader::new(log.as_bytes(), date);
+        let event = reader.next_event().unwrap().unwrap();
+        assert_eq!(event.name, "EXCP");
+        let event = reader.next_event().unwrap().unwrap();
+        assert_eq!(event.name, "CALL");
+        assert_eq!(event.properties[0].1.len(), long.len());
+        assert!(reader.next_event().unwrap().is_none());
+        assert!(reader.next_event().unwrap().is_none());
+    }
+}
@@ -0,0 +1,230 @@
+use std::borrow::Cow;
+
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use sha2::Sha256;
+
+use crate::{Event, OwnedEvent, sql};
+
+/// What to do with a property value.
+#[derive(Clone, Debug, Deserialize)]
+pub enum Action {
+    /// Drop the property from the event.
+    Remove,
+    /// Keep the property with an empty value.
+    Clear,
+    /// Replace the value with the given text.
+    Replace(String),
+    /// Replace the value with a token derived from it, see `Redactor::pseudonym`.
+    Pseudonymize,
+    /// Replace string, numeric and binary literals of SQL text with `?`.
+    SqlLiterals,
+}
+
+/// What to do with a regular expression match.
+#[derive(Clone, Debug, Deserialize)]
+pub enum MatchAction {
+    Replace(String),
+    Pseudonymize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PropRule {
+    /// Property name, case insensitive.
+    pub name: String,
+    pub action: Action,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PatternRule {
+    #[serde(deserialize_with = "deserialize_regex")]
+    pub r#match: Regex,
+    /// Properties the pattern is applied to, all properties if empty.
+    #[serde(default)]
+    pub props: Vec<String>,
+    pub action: MatchAction,
+    /// Prefix of pseudonyms produced by the pattern.
+    #[serde(default = "default_token_prefix")]
+    pub prefix: String,
+}
+
+fn default_token_prefix() -> String {
+    "Value".to_string()
+}
+
+fn deserialize_regex<'de, D>(deserializer: D) -> Result<Regex, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    Regex::new(&s).map_err(serde::de::Error::custom)
+}
+
+/// Redaction settings, deserializable from
+/// `{"Salt": "...", "Props": [{"Name": "Usr", "Action": "Pseudonymize"}],
+/// "Patterns": [{"Match": "\\d+\\.\\d+\\.\\d+\\.\\d+", "Action": "Pseudonymize", "Prefix": "IP"}]}`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct RedactConfig {
+    /// Secret key of pseudonyms, without it they can't be reversed by hashing known values.
+    pub salt: String,
+    pub props: Vec<PropRule>,
+    /// Applied after `props` to the remaining values.
+    pub patterns: Vec<PatternRule>,
+}
+
+/// Scrubs sensitive data from events before logs are shared.
+/// Pseudonyms are deterministic: the same value with the same salt always gives the same token,
+/// so e.g. all events of one user can still be correlated.
+#[derive(Deserialize)]
+#[serde(from = "RedactConfig")]
+pub struct Redactor {
+    config: RedactConfig,
+}
+
+impl Redactor {
+    pub fn new(config: RedactConfig) -> Self {
+        Self { config }
+    }
+
+    /// Returns a token like `Usr-1f0a3c5b7d9e` for `value`: the first 48 bits of
+    /// HMAC-SHA256 of the value with the salt as the key.
+    pub fn pseudonym(&self, prefix: &str, value: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.config.salt.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(value.as_bytes());
+        let hash = mac.finalize().into_bytes();
+        let mut token = format!("{prefix}-");
+        for b in &hash[..6] {
+            token.push_str(&format!("{b:02x}"));
+        }
+        token
+    }
+
+    pub fn redact(&self, event: &Event) -> OwnedEvent {
+        let mut result = event.to_owned_event();
+        self.redact_owned(&mut result);
+        result
+    }
+
+    pub fn redact_owned(&self, event: &mut OwnedEvent) {
+        event.properties.retain_mut(|(name, value)| {
+            let rule = self
+                .config
+                .props
+                .iter()
+                .find(|rule| rule.name.eq_ignore_ascii_case(name));
+            if let Some(rule) = rule {
+                match &rule.action {
+                    Action::Remove => return false,
+                    Action::Clear => value.clear(),
+                    Action::Replace(s) => s.clone_into(value),
+                    Action::Pseudonymize => *value = self.pseudonym(name, value),
+                    Action::SqlLiterals => *value = sql::replace_literals(value),
+                }
+            }
+
+            for pattern in self.config.patterns.iter() {
+                if !pattern.props.is_empty()
+                    && !pattern.props.iter().any(|x| x.eq_ignore_ascii_case(name))
+                {
+                    continue;
+                }
+                let replaced = match &pattern.action {
+                    MatchAction::Replace(s) => pattern.r#match.replace_all(value, s.as_str()),
+                    MatchAction::Pseudonymize => pattern
+                        .r#match
+                        .replace_all(value, |caps: &regex::Captures| {
+                            self.pseudonym(&pattern.prefix, &caps[0])
+                        }),
+                };
+                if let Cow::Owned(replaced) = replaced {
+                    *value = replaced;
+                }
+            }
+            true
+        });
+    }
+}
+
+impl From<RedactConfig> for Redactor {
+    fn from(config: RedactConfig) -> Self {
+        Self::new(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+
+    use super::{RedactConfig, Redactor};
+    use crate::{Event, LogStr};
+
+    #[test]
+    fn test_redact() {
+        let config: RedactConfig = serde_json::from_str(
+            r#"{
+                "Salt": "secret",
+                "Props": [
+                    {"Name": "usr", "Action": "Pseudonymize"},
+                    {"Name": "Descr", "Action": "Remove"},
+                    {"Name": "DataBase", "Action": {"Replace": "db"}},
+                    {"Name": "Sql", "Action": "SqlLiterals"}
+                ],
+                "Patterns": [
+                    {"Match": "\\b\\d{1,3}(\\.\\d{1,3}){3}\\b", "Action": "Pseudonymize", "Prefix": "IP"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let redactor = Redactor::new(config);
+
+        let properties = [
+            ("Usr", LogStr::new(b"Ivanov", '\0')),
+            ("Descr", LogStr::new(b"secret text", '\0')),
+            ("DataBase", LogStr::new(b"prod", '\0')),
+            ("Sql", LogStr::new(b"SELECT 1 WHERE x = 'abc'", '\0')),
+            ("ClientComputerName", LogStr::new(b"host 10.0.0.1", '\0')),
+        ];
+        let event = Event {
+            date: NaiveDate::from_ymd_opt(2024, 1, 4)
+                .and_then(|date| date.and_hms_opt(15, 0, 0))
+                .unwrap(),
+            duration: Duration::from_micros(1),
+            name: "DBMSSQL",
+            level: 0,
+            properties: &properties,
+        };
+
+        let redacted = redactor.redact(&event);
+        let usr = redactor.pseudonym("Usr", "Ivanov");
+        let ip = format!("host {}", redactor.pseudonym("IP", "10.0.0.1"));
+        assert_eq!(
+            redacted.properties,
+            [
+                ("Usr".to_string(), usr.clone()),
+                ("DataBase".to_string(), "db".to_string()),
+                ("Sql".to_string(), "SELECT ? WHERE x = ?".to_string()),
+                ("ClientComputerName".to_string(), ip),
+            ]
+        );
+        assert!(usr.starts_with("Usr-"));
+        assert_eq!(usr, redactor.redact(&event).properties[0].1);
+        assert_ne!(usr, redactor.pseudonym("Usr", "Petrov"));
+
+        // HMAC-SHA256 test vector of RFC 4231, test case 2
+        let redactor = Redactor::new(RedactConfig {
+            salt: "Jefe".to_string(),
+            ..RedactConfig::default()
+        });
+        assert_eq!(
+            redactor.pseudonym("Usr", "what do ya want for nothing?"),
+            "Usr-5bdcc146bf60"
+        );
+    }
+}
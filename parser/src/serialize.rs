@@ -0,0 +1,368 @@
+use chrono::{FixedOffset, NaiveDateTime, SecondsFormat};
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    ser::{SerializeMap, SerializeSeq},
+};
+
+use crate::{Event, LogStr};
+
+/// How event properties are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+pub enum PropsFormat {
+    /// `"Props": [["name", "value"], ...]`, the order and duplicates are kept as is.
+    #[default]
+    Pairs,
+    /// `"Props": {"name": "value", ...}`, values of duplicated properties are collapsed into an array.
+    Map,
+    /// Same as `Map`, but the properties are written next to `Date`, `Name`, etc.
+    /// A property whose name clashes with one of these fields is written with the `Prop` prefix.
+    Flatten,
+}
+
+/// How `Event::date` is written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+pub enum DateFormat {
+    /// Local time without offset: `2024-01-04T15:55:42.302093`.
+    #[default]
+    Naive,
+    /// RFC 3339 with the offset from `SerializeOptions::offset`: `2024-01-04T15:55:42.302093+03:00`.
+    Rfc3339,
+    /// Microseconds since the Unix epoch, the local time is converted using `SerializeOptions::offset`.
+    EpochMicros,
+}
+
+/// How `Event::duration` is written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+pub enum DurationFormat {
+    /// Integer number of microseconds.
+    #[default]
+    Micros,
+    /// Number of milliseconds with a fractional part.
+    Millis,
+}
+
+/// Options of the JSON representation of `Event`, deserializable from
+/// `{"Props": "Map", "Date": "Rfc3339", "Duration": "Millis", "Offset": "+03:00"}`,
+/// all fields are optional.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct SerializeOptions {
+    pub props: PropsFormat,
+    pub date: DateFormat,
+    pub duration: DurationFormat,
+    /// Offset of the local time of the log from UTC, the log itself does not contain it.
+    #[serde(deserialize_with = "deserialize_offset")]
+    pub offset: FixedOffset,
+}
+
+impl Default for SerializeOptions {
+    fn default() -> Self {
+        Self {
+            props: PropsFormat::default(),
+            date: DateFormat::default(),
+            duration: DurationFormat::default(),
+            offset: FixedOffset::east_opt(0).expect("zero offset is valid"),
+        }
+    }
+}
+
+impl SerializeOptions {
+    fn rfc3339(&self, date: NaiveDateTime) -> Option<String> {
+        let date = date.and_local_timezone(self.offset).single()?;
+        Some(date.to_rfc3339_opts(SecondsFormat::Micros, false))
+    }
+
+    fn epoch_micros(&self, date: NaiveDateTime) -> i64 {
+        (date - self.offset).and_utc().timestamp_micros()
+    }
+
+    /// The date as text in the configured format, for text outputs like CSV.
+    pub fn date_text(&self, date: NaiveDateTime) -> String {
+        match self.date {
+            DateFormat::Naive => date.format("%Y-%m-%dT%H:%M:%S%.f").to_string(),
+            DateFormat::Rfc3339 => self.rfc3339(date).unwrap_or_default(),
+            DateFormat::EpochMicros => self.epoch_micros(date).to_string(),
+        }
+    }
+
+    /// The duration in microseconds as text in the configured format.
+    pub fn duration_text(&self, micros: u128) -> String {
+        match self.duration {
+            DurationFormat::Micros => micros.to_string(),
+            DurationFormat::Millis => (micros as f64 / 1000.0).to_string(),
+        }
+    }
+}
+
+fn deserialize_offset<'de, D>(deserializer: D) -> Result<FixedOffset, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(serde::de::Error::custom)
+}
+
+impl Event<'_> {
+    /// Returns a wrapper that serializes the event according to `options`.
+    pub fn with_options<'e>(&'e self, options: &'e SerializeOptions) -> EventSerializer<'e> {
+        EventSerializer {
+            event: self,
+            options,
+        }
+    }
+}
+
+pub struct EventSerializer<'e> {
+    event: &'e Event<'e>,
+    options: &'e SerializeOptions,
+}
+
+const HEADER_FIELDS: [&str; 4] = ["Date", "Duration", "Name", "Level"];
+
+impl Serialize for EventSerializer<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let event = self.event;
+        let options = self.options;
+        let date = DateValue(event.date, options);
+        let duration = DurationValue(event.duration.as_micros(), options.duration);
+
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("Date", &date)?;
+        map.serialize_entry("Duration", &duration)?;
+        map.serialize_entry("Name", event.name)?;
+        map.serialize_entry("Level", &event.level)?;
+        match options.props {
+            PropsFormat::Pairs => map.serialize_entry("Props", event.properties)?,
+            PropsFormat::Map => map.serialize_entry("Props", &PropsMap(event.properties))?,
+            PropsFormat::Flatten => {
+                for_each_prop(event.properties, |name, value| {
+                    if HEADER_FIELDS.contains(&name) {
+                        map.serialize_entry(&flat_name(name, event.properties), &value)
+                    } else {
+                        map.serialize_entry(name, &value)
+                    }
+                })?;
+            }
+        }
+        map.end()
+    }
+}
+
+/// Name of a property clashing with a header field, prefixed with `Prop` until it differs from
+/// the names of the other properties.
+fn flat_name(name: &str, props: &[(&str, LogStr)]) -> String {
+    let mut flat = format!("Prop{name}");
+    while props.iter().any(|(x, _)| *x == flat) {
+        flat.insert_str(0, "Prop");
+    }
+    flat
+}
+
+struct DateValue<'o>(NaiveDateTime, &'o SerializeOptions);
+
+impl Serialize for DateValue<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let DateValue(date, options) = self;
+        match options.date {
+            DateFormat::Naive => date.serialize(serializer),
+            DateFormat::Rfc3339 => {
+                let date = options
+                    .rfc3339(*date)
+                    .ok_or_else(|| serde::ser::Error::custom("invalid date"))?;
+                serializer.serialize_str(&date)
+            }
+            DateFormat::EpochMicros => serializer.serialize_i64(options.epoch_micros(*date)),
+        }
+    }
+}
+
+struct DurationValue(u128, DurationFormat);
+
+impl Serialize for DurationValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.1 {
+            DurationFormat::Micros => serializer.serialize_u128(self.0),
+            DurationFormat::Millis => serializer.serialize_f64(self.0 as f64 / 1000.0),
+        }
+    }
+}
+
+/// Value of a property for the map formats: a single value or all values of a duplicated property.
+enum PropValue<'p, 'a> {
+    Single(&'p LogStr<'a>),
+    Multiple(&'a str, &'p [(&'a str, LogStr<'a>)]),
+}
+
+impl Serialize for PropValue<'_, '_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            PropValue::Single(value) => value.serialize(serializer),
+            PropValue::Multiple(name, props) => {
+                let mut seq = serializer.serialize_seq(None)?;
+                for (_, value) in props.iter().filter(|(x, _)| x == name) {
+                    seq.serialize_element(value)?;
+                }
+                seq.end()
+            }
+        }
+    }
+}
+
+/// Calls `f` once per distinct property name, in the order of the first occurrence.
+/// Property lists are short, so the quadratic search is cheaper than a hash map.
+fn for_each_prop<'p, 'a, F, E>(props: &'p [(&'a str, LogStr<'a>)], mut f: F) -> Result<(), E>
+where
+    F: FnMut(&'a str, PropValue<'p, 'a>) -> Result<(), E>,
+{
+    for (i, (name, value)) in props.iter().enumerate() {
+        if props[..i].iter().any(|(x, _)| x == name) {
+            continue;
+        }
+        let rest = &props[i..];
+        if rest[1..].iter().any(|(x, _)| x == name) {
+            f(name, PropValue::Multiple(name, rest))?;
+        } else {
+            f(name, PropValue::Single(value))?;
+        }
+    }
+    Ok(())
+}
+
+struct PropsMap<'p, 'a>(&'p [(&'a str, LogStr<'a>)]);
+
+impl Serialize for PropsMap<'_, '_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(None)?;
+        for_each_prop(self.0, |name, value| map.serialize_entry(name, &value))?;
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+    use serde_json::json;
+
+    use super::{DateFormat, DurationFormat, PropsFormat, SerializeOptions};
+    use crate::{Event, LogStr};
+
+    fn to_json(options: &SerializeOptions) -> serde_json::Value {
+        let properties = [
+            ("process", LogStr::new(b"rphost", '\0')),
+            ("Context", LogStr::new(b"first", '\0')),
+            ("Name", LogStr::new(b"clash", '\0')),
+            ("Context", LogStr::new(b"it''s", '\'')),
+        ];
+        let event = Event {
+            date: NaiveDate::from_ymd_opt(2024, 1, 4)
+                .and_then(|date| date.and_hms_micro_opt(15, 55, 42, 302093))
+                .unwrap(),
+            duration: Duration::from_micros(1500),
+            name: "CALL",
+            level: 1,
+            properties: &properties,
+        };
+        serde_json::to_value(event.with_options(options)).unwrap()
+    }
+
+    #[test]
+    fn test_default() {
+        let value = to_json(&SerializeOptions::default());
+        assert_eq!(value["Date"], "2024-01-04T15:55:42.302093");
+        assert_eq!(value["Duration"], 1500);
+        assert_eq!(value["Props"][3], json!(["Context", "it's"]));
+    }
+
+    #[test]
+    fn test_text() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 4)
+            .and_then(|date| date.and_hms_micro_opt(15, 55, 42, 302093))
+            .unwrap();
+        let mut options = SerializeOptions::default();
+        assert_eq!(options.date_text(date), "2024-01-04T15:55:42.302093");
+        assert_eq!(options.duration_text(1500), "1500");
+        options =
+            serde_json::from_str(r#"{"Date":"Rfc3339","Duration":"Millis","Offset":"+03:00"}"#)
+                .unwrap();
+        assert_eq!(options.date_text(date), "2024-01-04T15:55:42.302093+03:00");
+        assert_eq!(options.duration_text(1500), "1.5");
+        options.date = DateFormat::EpochMicros;
+        assert_eq!(options.date_text(date), "1704372942302093");
+    }
+
+    #[test]
+    fn test_map() {
+        let options: SerializeOptions = serde_json::from_str(
+            r#"{"Props":"Map","Date":"Rfc3339","Duration":"Millis","Offset":"+03:00"}"#,
+        )
+        .unwrap();
+        assert_eq!(options.props, PropsFormat::Map);
+        let value = to_json(&options);
+        assert_eq!(value["Date"], "2024-01-04T15:55:42.302093+03:00");
+        assert_eq!(value["Duration"], 1.5);
+        assert_eq!(
+            value["Props"],
+            json!({"process": "rphost", "Context": ["first", "it's"], "Name": "clash"})
+        );
+    }
+
+    #[test]
+    fn test_flatten() {
+        let options = SerializeOptions {
+            props: PropsFormat::Flatten,
+            date: DateFormat::EpochMicros,
+            duration: DurationFormat::Micros,
+            offset: "+03:00".parse().unwrap(),
+        };
+        let value = to_json(&options);
+        assert_eq!(value["Date"], 1704372942302093i64);
+        assert_eq!(value["Name"], "CALL");
+        assert_eq!(value["PropName"], "clash");
+        assert_eq!(value["process"], "rphost");
+        assert_eq!(value["Context"], json!(["first", "it's"]));
+    }
+
+    #[test]
+    fn test_flatten_clash() {
+        let options = SerializeOptions {
+            props: PropsFormat::Flatten,
+            ..SerializeOptions::default()
+        };
+        let properties = [
+            ("Name", LogStr::new(b"clash", '\0')),
+            ("PropName", LogStr::new(b"prop", '\0')),
+            ("PropPropName", LogStr::new(b"prop prop", '\0')),
+        ];
+        let event = Event {
+            date: NaiveDate::from_ymd_opt(2024, 1, 4)
+                .and_then(|date| date.and_hms_opt(15, 0, 0))
+                .unwrap(),
+            duration: Duration::from_micros(1),
+            name: "CALL",
+            level: 1,
+            properties: &properties,
+        };
+        let json = serde_json::to_string(&event.with_options(&options)).unwrap();
+        assert_eq!(
+            json,
+            r#"{"Date":"2024-01-04T15:00:00","Duration":1,"Name":"CALL","Level":1,"PropPropPropName":"clash","PropName":"prop","PropPropName":"prop prop"}"#
+        );
+    }
+}
@@ -0,0 +1,396 @@
+use std::{borrow::Cow, fmt};
+
+use crate::hash::Fnv64;
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Token<'a> {
+    /// String literal including quotes and the `N`/`E` prefix.
+    Str(&'a str),
+    /// Double-quoted text including quotes: an identifier of PostgreSQL or a string literal of
+    /// the 1C query language, they can't be told apart by the text.
+    Quoted(&'a str),
+    /// Numeric or binary (`0x...`) literal.
+    Number(&'a str),
+    /// Identifier, keyword or parameter: `_Fld123`, `SELECT`, `@P1`, `$1`, `#tt12`, `[dbo]`.
+    Word(&'a str),
+    Space(&'a str),
+    Punct(&'a str),
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || matches!(ch, '_' | '@' | '#' | '$')
+}
+
+/// Splits SQL text (MS SQL, PostgreSQL or the 1C query language) into tokens,
+/// the concatenation of all tokens is the original text.
+pub(crate) struct Tokens<'a> {
+    sql: &'a str,
+    pos: usize,
+}
+
+pub(crate) fn tokens(sql: &str) -> Tokens<'_> {
+    Tokens { sql, pos: 0 }
+}
+
+impl<'a> Tokens<'a> {
+    fn take_while(&mut self, start: usize, f: impl Fn(char) -> bool) -> &'a str {
+        let rest = &self.sql[self.pos..];
+        let len = rest.find(|ch| !f(ch)).unwrap_or(rest.len());
+        self.pos += len;
+        &self.sql[start..self.pos]
+    }
+
+    /// Skips to the closing `quote`, a doubled quote is a part of the value.
+    fn take_quoted(&mut self, start: usize, quote: char) -> &'a str {
+        let bytes = self.sql.as_bytes();
+        let quote = quote as u8;
+        while self.pos < bytes.len() {
+            let ch = bytes[self.pos];
+            self.pos += 1;
+            if ch == quote {
+                if bytes.get(self.pos) == Some(&quote) {
+                    self.pos += 1;
+                } else {
+                    break;
+                }
+            }
+        }
+        &self.sql[start..self.pos]
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.pos;
+        let ch = self.sql[start..].chars().next()?;
+        self.pos += ch.len_utf8();
+
+        let token = match ch {
+            '\'' => Token::Str(self.take_quoted(start, '\'')),
+            '"' => Token::Quoted(self.take_quoted(start, '"')),
+            '[' => Token::Word(self.take_quoted(start, ']')),
+            '0' if self.sql[self.pos..].starts_with(['x', 'X']) => {
+                self.pos += 1;
+                Token::Number(self.take_while(start, |ch| ch.is_ascii_hexdigit()))
+            }
+            '0'..='9' => {
+                self.take_while(start, |ch| ch.is_ascii_digit());
+                let rest = &self.sql.as_bytes()[self.pos..];
+                if rest.len() > 1 && rest[0] == b'.' && rest[1].is_ascii_digit() {
+                    self.pos += 1;
+                    self.take_while(start, |ch| ch.is_ascii_digit());
+                }
+                Token::Number(&self.sql[start..self.pos])
+            }
+            ch if ch.is_whitespace() => Token::Space(self.take_while(start, char::is_whitespace)),
+            ch if is_word_char(ch) => {
+                let word = self.take_while(start, is_word_char);
+                if matches!(word, "N" | "n" | "E" | "e") && self.sql[self.pos..].starts_with('\'') {
+                    self.pos += 1;
+                    Token::Str(self.take_quoted(start, '\''))
+                } else {
+                    Token::Word(word)
+                }
+            }
+            _ => Token::Punct(&self.sql[start..self.pos]),
+        };
+        Some(token)
+    }
+}
+
+/// Replaces string, numeric and binary literals with `?`, the rest of the text is kept as is.
+/// Double-quoted text is replaced too, so string literals of the 1C query language don't leak,
+/// though in PostgreSQL it is an identifier.
+pub fn replace_literals(sql: &str) -> String {
+    let mut result = String::with_capacity(sql.len());
+    for token in tokens(sql) {
+        match token {
+            Token::Str(_) | Token::Number(_) | Token::Quoted(_) => result.push('?'),
+            Token::Word(s) | Token::Space(s) | Token::Punct(s) => result.push_str(s),
+        }
+    }
+    result
+}
+
+/// Cuts the parameter values the platform appends to the query text of DBMSSQL events:
+/// `...\np_0: 10\np_1: 'abc'`.
+fn strip_params(sql: &str) -> &str {
+    let mut pos = 0;
+    for line in sql.split_inclusive('\n') {
+        if pos > 0 && line.trim_start().starts_with("p_0:") {
+            return &sql[..pos];
+        }
+        pos += line.len();
+    }
+    sql
+}
+
+fn normalize_word(word: &str) -> Cow<'_, str> {
+    let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+
+    if let Some(rest) = word.strip_prefix('#') {
+        // temporary table of MS SQL: #tt12
+        let name = rest.trim_end_matches(|ch: char| ch.is_ascii_digit());
+        return Cow::Owned(format!("#{}", name.to_lowercase()));
+    }
+    let lower = word.to_lowercase();
+    if let Some(rest) = lower.strip_prefix("@p").or_else(|| lower.strip_prefix('$'))
+        && is_number(rest)
+    {
+        // parameter of MS SQL or PostgreSQL: @P1, $1
+        return Cow::Borrowed("?");
+    }
+    if let Some(rest) = lower.strip_prefix("tt")
+        && is_number(rest)
+    {
+        // temporary table of PostgreSQL: pg_temp.tt12
+        return Cow::Borrowed("tt");
+    }
+    Cow::Owned(lower)
+}
+
+/// Lowercased keywords of SQL and the 1C query language followed by an operand, a minus after
+/// them is unary. Sorted for the binary search.
+const KEYWORDS_BEFORE_OPERAND: [&str; 36] = [
+    "and",
+    "between",
+    "case",
+    "else",
+    "having",
+    "in",
+    "is",
+    "like",
+    "limit",
+    "not",
+    "offset",
+    "on",
+    "or",
+    "return",
+    "select",
+    "set",
+    "then",
+    "top",
+    "values",
+    "when",
+    "where",
+    "в",
+    "выбор",
+    "выбрать",
+    "где",
+    "есть",
+    "и",
+    "или",
+    "иначе",
+    "когда",
+    "между",
+    "не",
+    "первые",
+    "по",
+    "подобно",
+    "тогда",
+];
+
+/// Lowercased `IN` of SQL and the 1C query language, lists in parentheses after it are collapsed.
+const IN_KEYWORDS: [&str; 2] = ["in", "в"];
+
+/// Normalizes query text, so that executions of the same query with different parameters
+/// give the same text: literals and parameters are replaced with `?`, lists like `IN (?, ?, ?)`
+/// are collapsed to `IN (?)`, numbers of temporary tables are removed, the parameter block
+/// of DBMSSQL events is cut, identifiers and keywords are lowercased and whitespace is
+/// kept only between words. A unary minus is a part of the number, so `-1` is `?` too.
+/// Double-quoted identifiers are kept as is, in the 1C query language they are string
+/// literals, so such queries differ by the literals.
+pub fn normalize(sql: &str) -> String {
+    let mut result = String::with_capacity(sql.len());
+    let mut prev_word = false;
+    // the previous token ends an operand, so a following minus is binary
+    let mut operand = false;
+    // the previous token is `IN`
+    let mut prev_in = false;
+    // for every open parenthesis, whether it is a list of `IN`
+    let mut lists = Vec::new();
+    let mut tokens = tokens(strip_params(sql))
+        .filter(|token| !matches!(token, Token::Space(_)))
+        .peekable();
+    while let Some(token) = tokens.next() {
+        let (text, word) = match token {
+            Token::Punct("-") if !operand && matches!(tokens.peek(), Some(Token::Number(_))) => {
+                continue;
+            }
+            Token::Space(_) => continue,
+            Token::Str(_) | Token::Number(_) => (Cow::Borrowed("?"), true),
+            Token::Quoted(s) => (Cow::Borrowed(s), true),
+            Token::Word(s) => (normalize_word(s), true),
+            Token::Punct(s) => (Cow::Borrowed(s), false),
+        };
+        operand = (word && KEYWORDS_BEFORE_OPERAND.binary_search(&&*text).is_err()) || text == ")";
+        match &*text {
+            "(" => lists.push(prev_in),
+            ")" => {
+                lists.pop();
+            }
+            _ => (),
+        }
+        prev_in = IN_KEYWORDS.contains(&&*text);
+        if text == "?" && result.ends_with("?,") && lists.last() == Some(&true) {
+            result.pop();
+            continue;
+        }
+        if word && prev_word {
+            result.push(' ');
+        }
+        result.push_str(&text);
+        prev_word = word;
+    }
+    result
+}
+
+/// Normalized query text and its hash, see `normalize`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Fingerprint {
+    pub text: String,
+    /// Stable between runs and builds, so it can be stored.
+    pub hash: u64,
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.hash)
+    }
+}
+
+pub fn fingerprint(sql: &str) -> Fingerprint {
+    let text = normalize(sql);
+    let hash = Fnv64::new().write(text.as_bytes()).finish();
+    Fingerprint { text, hash }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        IN_KEYWORDS, KEYWORDS_BEFORE_OPERAND, Token, fingerprint, normalize, replace_literals,
+        tokens,
+    };
+
+    #[test]
+    fn test_tokens() {
+        let sql = "SELECT T1._Fld12 FROM #tt3 WHERE x=N'it''s'AND y>=-1.5 OR z=0x0A1b";
+        let tokens: Vec<_> = tokens(sql).collect();
+        assert_eq!(tokens[0], Token::Word("SELECT"));
+        assert_eq!(tokens[2], Token::Word("T1"));
+        assert_eq!(tokens[3], Token::Punct("."));
+        assert!(tokens.contains(&Token::Word("#tt3")));
+        assert!(tokens.contains(&Token::Str("N'it''s'")));
+        assert!(tokens.contains(&Token::Number("1.5")));
+        assert!(tokens.contains(&Token::Number("0x0A1b")));
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|token| match token {
+                    Token::Str(s)
+                    | Token::Quoted(s)
+                    | Token::Number(s)
+                    | Token::Word(s)
+                    | Token::Space(s)
+                    | Token::Punct(s) => *s,
+                })
+                .collect::<String>(),
+            sql
+        );
+    }
+
+    #[test]
+    fn test_replace_literals() {
+        assert_eq!(
+            replace_literals(
+                "SELECT TOP 1 _Fld1 FROM _Reference12 WHERE _Description = 'Иванов' AND _IDRRef = 0x8A3F"
+            ),
+            "SELECT TOP ? _Fld1 FROM _Reference12 WHERE _Description = ? AND _IDRRef = ?"
+        );
+        assert_eq!(replace_literals("select $1, 'unterminated"), "select $1, ?");
+        assert_eq!(
+            replace_literals(
+                "ВЫБРАТЬ Код ИЗ Справочник.Товары ГДЕ Наименование = \"Стол \"\"Люкс\"\"\""
+            ),
+            "ВЫБРАТЬ Код ИЗ Справочник.Товары ГДЕ Наименование = ?"
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize(
+                "SELECT\r\nT1._Fld1 ,T1._Fld2\r\nFROM dbo._Reference12 T1\r\nWHERE (T1._IDRRef IN (@P1, @P2,@P3)) AND T1._Code = N'0001'\r\np_0: 0x8A3F\r\np_1: 0x8A40\r\np_2: 0x8A41\r\n"
+            ),
+            "select t1._fld1,t1._fld2 from dbo._reference12 t1 where(t1._idrref in(?))and t1._code=?"
+        );
+        assert_eq!(
+            normalize("INSERT INTO #tt15 WITH(TABLOCK) (_Q_000_F_000) SELECT 1"),
+            "insert into #tt with(tablock)(_q_000_f_000)select ?"
+        );
+        assert_eq!(
+            normalize("SELECT T1.Fld FROM pg_temp.tt7 T1 WHERE T1.Fld = $1 LIMIT 10"),
+            "select t1.fld from pg_temp.tt t1 where t1.fld=? limit ?"
+        );
+        assert_eq!(
+            normalize("SELECT x - 1, (x) - 2 FROM t WHERE y = -1 AND z IN (-1, - 2, 3)"),
+            "select x-?,(x)-? from t where y=? and z in(?)"
+        );
+        assert_eq!(fingerprint("SELECT -1"), fingerprint("SELECT 1"));
+        assert_eq!(normalize("ВЫБРАТЬ -1 ГДЕ А = -2"), "выбрать ? где а=?");
+        assert_eq!(
+            normalize("SELECT x FROM t WHERE x IN (-1, -2) OR y LIKE -1 OR z IN -3"),
+            "select x from t where x in(?)or y like ? or z in ?"
+        );
+        assert_eq!(
+            normalize("SELECT CASE WHEN x IS NULL THEN -1 ELSE -2 END FROM a JOIN b ON a.x = -1"),
+            "select case when x is null then ? else ? end from a join b on a.x=?"
+        );
+        assert_eq!(
+            normalize("ВЫБРАТЬ А ГДЕ А В (-1, 2) И Б ПОДОБНО -1"),
+            "выбрать а где а в(?)и б подобно ?"
+        );
+    }
+
+    #[test]
+    fn test_keywords() {
+        assert!(KEYWORDS_BEFORE_OPERAND.is_sorted());
+        assert!(
+            IN_KEYWORDS
+                .iter()
+                .all(|x| KEYWORDS_BEFORE_OPERAND.contains(x))
+        );
+    }
+
+    #[test]
+    fn test_lists() {
+        assert_ne!(
+            fingerprint("INSERT INTO t VALUES (1, 2)"),
+            fingerprint("INSERT INTO t VALUES (1, 2, 3)")
+        );
+        assert_eq!(
+            normalize("INSERT INTO t VALUES (1, 2) ; SELECT f(1, 2) FROM t WHERE x IN (1, (2), 3)"),
+            "insert into t values(?,?);select f(?,?)from t where x in(?,(?),?)"
+        );
+        assert_eq!(
+            normalize("SELECT \"Fld\" FROM \"Table\" WHERE \"Fld\" = $1"),
+            "select \"Fld\" from \"Table\" where \"Fld\"=?"
+        );
+        assert_ne!(
+            fingerprint("SELECT a FROM \"T1\""),
+            fingerprint("SELECT a FROM \"T2\"")
+        );
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let a = fingerprint("SELECT * FROM #tt1 WHERE x IN (1, 2, 3)");
+        let b = fingerprint("select *\n  from #tt25\n where x in (4)");
+        let c = fingerprint("select * from #tt1 where y in (4)");
+        assert_eq!(a, b);
+        assert_ne!(a.hash, c.hash);
+        assert_eq!(a.to_string().len(), 16);
+    }
+}
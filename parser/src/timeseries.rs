@@ -0,0 +1,197 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    time::Duration,
+};
+
+use chrono::{DateTime, NaiveDateTime};
+use serde::Serialize;
+
+use crate::{Event, aggregate::GroupKey};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Bucket {
+    pub count: u64,
+    /// Microseconds.
+    pub total: u128,
+    /// Microseconds.
+    pub max: u64,
+}
+
+/// A row of `TimeSeries::points`, durations are in microseconds.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Point {
+    pub time: NaiveDateTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    pub count: u64,
+    pub total: u128,
+    pub max: u64,
+}
+
+/// Counts events and their durations in fixed time intervals, optionally broken down by a key.
+/// Events may come in any order, so files of several processes can be fed one after another.
+pub struct TimeSeries {
+    interval: i64,
+    breakdown: Option<GroupKey>,
+    start_time: bool,
+    buckets: BTreeMap<(NaiveDateTime, String), Bucket>,
+}
+
+impl TimeSeries {
+    /// `interval` is rounded down to microseconds, at least one microsecond.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval: (interval.as_micros() as i64).max(1),
+            breakdown: None,
+            start_time: false,
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    /// Splits every interval by the value of `key`, e.g. the event name.
+    pub fn breakdown(mut self, key: GroupKey) -> Self {
+        self.breakdown = Some(key);
+        self
+    }
+
+    /// Puts events into buckets by their start time (`date - duration`) instead of
+    /// `date`, which is the end of the event. An event with a start out of range, i.e. with
+    /// a malformed duration, goes by `date`.
+    pub fn start_time(mut self, value: bool) -> Self {
+        self.start_time = value;
+        self
+    }
+
+    fn bucket_time(&self, date: NaiveDateTime) -> NaiveDateTime {
+        let micros = date.and_utc().timestamp_micros();
+        let micros = micros - micros.rem_euclid(self.interval);
+        DateTime::from_timestamp_micros(micros)
+            .map(|x| x.naive_utc())
+            .unwrap_or(date)
+    }
+
+    pub fn add(&mut self, event: &Event) {
+        let date = if self.start_time {
+            event.start().unwrap_or(event.date)
+        } else {
+            event.date
+        };
+        let key = self
+            .breakdown
+            .as_ref()
+            .map(|x| x.value(event))
+            .unwrap_or_default();
+        let duration = event.duration.as_micros().min(u64::MAX as u128) as u64;
+
+        let bucket = self
+            .buckets
+            .entry((self.bucket_time(date), key))
+            .or_default();
+        bucket.count += 1;
+        bucket.total += duration as u128;
+        bucket.max = bucket.max.max(duration);
+    }
+
+    pub fn merge(&mut self, other: &TimeSeries) {
+        for (key, x) in other.buckets.iter() {
+            let bucket = self.buckets.entry(key.clone()).or_default();
+            bucket.count += x.count;
+            bucket.total += x.total;
+            bucket.max = bucket.max.max(x.max);
+        }
+    }
+
+    /// Non-empty buckets ordered by time and key.
+    pub fn points(&self) -> Vec<Point> {
+        self.buckets
+            .iter()
+            .map(|((time, key), bucket)| Point {
+                time: *time,
+                key: self.breakdown.as_ref().map(|_| key.clone()),
+                count: bucket.count,
+                total: bucket.total,
+                max: bucket.max,
+            })
+            .collect()
+    }
+
+    /// Writes `Time,Key,Count,Total,Max` rows with a header, `Key` only if there is a breakdown.
+    pub fn write_csv(&self, w: &mut impl Write) -> io::Result<()> {
+        if self.breakdown.is_some() {
+            w.write_all(b"Time,Key,Count,Total,Max\r\n")?;
+        } else {
+            w.write_all(b"Time,Count,Total,Max\r\n")?;
+        }
+        for ((time, key), bucket) in self.buckets.iter() {
+            write!(w, "{}", time.format("%Y-%m-%dT%H:%M:%S%.f"))?;
+            if self.breakdown.is_some() {
+                write!(w, ",\"{}\"", key.replace('"', "\"\""))?;
+            }
+            write!(w, ",{},{},{}\r\n", bucket.count, bucket.total, bucket.max)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+
+    use super::TimeSeries;
+    use crate::{Event, aggregate::GroupKey};
+
+    fn add(series: &mut TimeSeries, name: &str, sec: u32, micro: u32, duration: u64) {
+        series.add(&Event {
+            date: NaiveDate::from_ymd_opt(2024, 1, 4)
+                .and_then(|date| date.and_hms_micro_opt(15, 0, sec, micro))
+                .unwrap(),
+            duration: Duration::from_micros(duration),
+            name,
+            level: 0,
+            properties: &[],
+        });
+    }
+
+    #[test]
+    fn test_series() {
+        let mut series = TimeSeries::new(Duration::from_secs(1)).breakdown(GroupKey::Name);
+        add(&mut series, "CALL", 1, 100, 10);
+        add(&mut series, "CALL", 1, 900_000, 30);
+        add(&mut series, "EXCP", 1, 0, 0);
+        add(&mut series, "CALL", 0, 5, 7);
+
+        let points = series.points();
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[0].time.to_string(), "2024-01-04 15:00:00");
+        assert_eq!(points[1].key.as_deref(), Some("CALL"));
+        assert_eq!(
+            (points[1].count, points[1].total, points[1].max),
+            (2, 40, 30)
+        );
+
+        let mut csv = Vec::new();
+        series.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(
+            csv.starts_with("Time,Key,Count,Total,Max\r\n2024-01-04T15:00:00,\"CALL\",1,7,7\r\n")
+        );
+    }
+
+    #[test]
+    fn test_start_time() {
+        let mut series = TimeSeries::new(Duration::from_secs(60)).start_time(true);
+        add(&mut series, "CALL", 1, 0, 2_000_000);
+        add(&mut series, "CALL", 30, 0, 0);
+        add(&mut series, "CALL", 40, 0, u64::MAX);
+        let points = series.points();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].time.to_string(), "2024-01-04 14:59:00");
+        assert_eq!(points[0].key, None);
+        assert_eq!(points[1].count, 2);
+        assert_eq!(points[1].max, u64::MAX);
+    }
+}
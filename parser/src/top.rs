@@ -0,0 +1,275 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BTreeMap, BinaryHeap, HashMap},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::{Event, OwnedEvent, aggregate::GroupKey};
+
+struct Entry {
+    /// Order of arrival, makes the order of events with equal durations stable.
+    seq: u64,
+    event: OwnedEvent,
+}
+
+impl Entry {
+    fn rank(&self) -> (Duration, Reverse<u64>) {
+        (self.event.duration, Reverse(self.seq))
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.rank() == other.rank()
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+/// The slowest events of a group, the slowest first.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TopGroup {
+    pub key: Vec<String>,
+    pub events: Vec<OwnedEvent>,
+}
+
+struct Group {
+    /// Unique, the sequence number of the first event of the group.
+    id: u64,
+    /// Duration of the slowest event.
+    max: Duration,
+    heap: BinaryHeap<Reverse<Entry>>,
+}
+
+/// Default limit of the number of groups of `TopN`.
+pub const DEFAULT_MAX_GROUPS: usize = 10_000;
+
+fn owned_event(props: &[String], event: &Event) -> OwnedEvent {
+    OwnedEvent {
+        date: event.date,
+        duration: event.duration,
+        name: event.name.to_string(),
+        level: event.level,
+        properties: event
+            .properties
+            .iter()
+            .filter(|(name, _)| {
+                props.is_empty() || props.iter().any(|x| x.eq_ignore_ascii_case(name))
+            })
+            .map(|(name, value)| (name.to_string(), value.str().into_owned()))
+            .collect(),
+    }
+}
+
+/// Keeps the `n` slowest events of every group. An event is copied only if it gets into the top,
+/// and only the properties listed in `props` are kept, so the memory depends on `n`, the number
+/// of groups and the size of the kept properties, not on the amount of scanned logs.
+/// The number of groups is limited by `with_max_groups`: when a new group doesn't fit, the group
+/// with the fastest slowest event is dropped, or the new one if it is even faster.
+pub struct TopN {
+    n: usize,
+    keys: Vec<GroupKey>,
+    props: Vec<String>,
+    seq: u64,
+    max_groups: usize,
+    groups: HashMap<Vec<String>, Group>,
+    /// Keys of the groups by the duration of their slowest event, the first one is evicted.
+    by_max: BTreeMap<(Duration, u64), Vec<String>>,
+    /// Key of the current event, reused, so rejected events allocate nothing for it.
+    key: Vec<String>,
+}
+
+impl TopN {
+    /// `keys` may be empty for a single top of all events, `props` may be empty to keep all properties.
+    pub fn new(n: usize, keys: Vec<GroupKey>, props: Vec<String>) -> Self {
+        Self {
+            n,
+            keys,
+            props,
+            seq: 0,
+            max_groups: DEFAULT_MAX_GROUPS,
+            groups: HashMap::new(),
+            by_max: BTreeMap::new(),
+            key: Vec::new(),
+        }
+    }
+
+    pub fn with_max_groups(mut self, max_groups: usize) -> Self {
+        self.max_groups = max_groups;
+        self
+    }
+
+    pub fn add(&mut self, event: &Event) {
+        if self.n == 0 || self.max_groups == 0 {
+            return;
+        }
+        self.seq += 1;
+        self.key.resize_with(self.keys.len(), String::new);
+        for (key, value) in self.keys.iter().zip(self.key.iter_mut()) {
+            value.clear();
+            key.write_value(event, value);
+        }
+
+        if let Some(group) = self.groups.get_mut(&self.key[..]) {
+            if group.heap.len() >= self.n {
+                if group
+                    .heap
+                    .peek()
+                    .is_some_and(|x| event.duration <= x.0.event.duration)
+                {
+                    return;
+                }
+                group.heap.pop();
+            }
+            group.heap.push(Reverse(Entry {
+                seq: self.seq,
+                event: owned_event(&self.props, event),
+            }));
+            if event.duration > group.max {
+                let key = self.by_max.remove(&(group.max, group.id));
+                group.max = event.duration;
+                self.by_max
+                    .insert((group.max, group.id), key.unwrap_or_default());
+            }
+            return;
+        }
+
+        if self.groups.len() >= self.max_groups {
+            let Some(fastest) = self.by_max.first_entry() else {
+                return;
+            };
+            if event.duration <= fastest.key().0 {
+                return;
+            }
+            self.groups.remove(&fastest.remove());
+        }
+        let mut heap = BinaryHeap::new();
+        heap.push(Reverse(Entry {
+            seq: self.seq,
+            event: owned_event(&self.props, event),
+        }));
+        self.by_max
+            .insert((event.duration, self.seq), self.key.clone());
+        self.groups.insert(
+            self.key.clone(),
+            Group {
+                id: self.seq,
+                max: event.duration,
+                heap,
+            },
+        );
+    }
+
+    /// All groups ordered by the duration of their slowest event, descending.
+    pub fn results(&self) -> Vec<TopGroup> {
+        let mut groups: Vec<_> = self
+            .groups
+            .iter()
+            .map(|(key, group)| {
+                let mut entries: Vec<_> = group.heap.iter().map(|x| &x.0).collect();
+                entries.sort_by(|a, b| b.cmp(a));
+                TopGroup {
+                    key: key.clone(),
+                    events: entries.into_iter().map(|x| x.event.clone()).collect(),
+                }
+            })
+            .collect();
+        groups.sort_by(|a, b| {
+            let max = |x: &TopGroup| x.events.first().map(|x| x.duration);
+            max(b).cmp(&max(a)).then_with(|| a.key.cmp(&b.key))
+        });
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+
+    use super::TopN;
+    use crate::{Event, LogStr, aggregate::GroupKey};
+
+    #[test]
+    fn test_top() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 4)
+            .and_then(|date| date.and_hms_opt(15, 0, 0))
+            .unwrap();
+        let mut top = TopN::new(2, vec![GroupKey::Name], vec!["Sql".to_string()]);
+        for (name, duration, sql) in [
+            ("DBMSSQL", 10, "a"),
+            ("DBMSSQL", 30, "b"),
+            ("CALL", 5, ""),
+            ("DBMSSQL", 20, "c"),
+            ("DBMSSQL", 20, "d"),
+            ("DBMSSQL", 5, "e"),
+        ] {
+            let properties = [
+                ("process", LogStr::new(b"rphost", '\0')),
+                ("Sql", LogStr::new(sql.as_bytes(), '\0')),
+            ];
+            top.add(&Event {
+                date,
+                duration: Duration::from_micros(duration),
+                name,
+                level: 0,
+                properties: &properties,
+            });
+        }
+
+        let results = top.results();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].key, ["DBMSSQL"]);
+        let events: Vec<_> = results[0]
+            .events
+            .iter()
+            .map(|x| (x.duration.as_micros(), x.properties[0].1.as_str()))
+            .collect();
+        assert_eq!(events, [(30, "b"), (20, "c")]);
+        assert_eq!(results[0].events[0].properties.len(), 1);
+        assert_eq!(results[1].events.len(), 1);
+    }
+
+    #[test]
+    fn test_max_groups() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 4)
+            .and_then(|date| date.and_hms_opt(15, 0, 0))
+            .unwrap();
+        let mut top = TopN::new(2, vec![GroupKey::Name], Vec::new()).with_max_groups(2);
+        for (name, duration) in [
+            ("A", 10),
+            ("B", 30),
+            ("C", 5),
+            ("A", 12),
+            ("D", 20),
+            ("C", 6),
+        ] {
+            top.add(&Event {
+                date,
+                duration: Duration::from_micros(duration),
+                name,
+                level: 0,
+                properties: &[],
+            });
+        }
+        let results = top.results();
+        let keys: Vec<_> = results.iter().map(|x| x.key[0].as_str()).collect();
+        assert_eq!(keys, ["B", "D"]);
+    }
+}
@@ -1,6 +1,6 @@
 use std::{borrow::Cow, time::Duration};
 
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, TimeDelta};
 use serde::{Serialize, Serializer, ser::SerializeStruct};
 
 pub struct Event<'a> {
@@ -8,7 +8,37 @@ pub struct Event<'a> {
     pub duration: Duration,
     pub name: &'a str,
     pub level: u32,
-    pub properties: &'a[(&'a str, LogStr<'a>)]
+    pub properties: &'a [(&'a str, LogStr<'a>)],
+}
+
+impl<'a> Event<'a> {
+    /// Returns the first property with the given name, case insensitive.
+    pub fn get_prop(&self, name: &str) -> Option<&LogStr<'a>> {
+        self.properties
+            .iter()
+            .find(|(x, _)| x.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Start of the event, `date` is its end. `None` if a malformed duration goes out of range.
+    pub fn start(&self) -> Option<NaiveDateTime> {
+        self.date
+            .checked_sub_signed(TimeDelta::from_std(self.duration).ok()?)
+    }
+
+    pub fn to_owned_event(&self) -> OwnedEvent {
+        OwnedEvent {
+            date: self.date,
+            duration: self.duration,
+            name: self.name.to_owned(),
+            level: self.level,
+            properties: self
+                .properties
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.str().into_owned()))
+                .collect(),
+        }
+    }
 }
 
 impl Serialize for Event<'_> {
@@ -28,6 +58,49 @@ impl Serialize for Event<'_> {
     }
 }
 
+/// An event that does not borrow the parser buffer, e.g. to keep it after the parsing callback returns.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OwnedEvent {
+    pub date: NaiveDateTime,
+    pub duration: Duration,
+    pub name: String,
+    pub level: u32,
+    pub properties: Vec<(String, String)>,
+}
+
+impl OwnedEvent {
+    /// Calls `f` with a borrowed view of the event, so it can be passed to anything that accepts `Event`.
+    pub fn with_event<R>(&self, f: impl FnOnce(&Event) -> R) -> R {
+        let properties: Vec<_> = self
+            .properties
+            .iter()
+            .map(|(name, value)| (name.as_str(), LogStr::new(value.as_bytes(), '\0')))
+            .collect();
+        f(&Event {
+            date: self.date,
+            duration: self.duration,
+            name: &self.name,
+            level: self.level,
+            properties: &properties,
+        })
+    }
+}
+
+impl From<&Event<'_>> for OwnedEvent {
+    fn from(value: &Event<'_>) -> Self {
+        value.to_owned_event()
+    }
+}
+
+impl Serialize for OwnedEvent {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.with_event(|event| event.serialize(serializer))
+    }
+}
+
 pub struct LogStr<'a> {
     str: &'a [u8],
     replace_char: char,
@@ -0,0 +1,280 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{NaiveDate, NaiveDateTime, Timelike};
+
+use crate::{Event, OwnedEvent};
+
+const BOM: [u8; 3] = [0xEF, 0xBB, 0xBF];
+
+/// Writes events in the native tech log format, so the output can be read back by `parse_file`.
+pub struct LogWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> LogWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+
+    /// Writes the UTF-8 BOM, the platform writes it at the beginning of every log file.
+    pub fn write_bom(&mut self) -> io::Result<()> {
+        self.inner.write_all(&BOM)
+    }
+
+    /// Writes a single record, the minutes and seconds are taken from `event.date`,
+    /// the hour and the day are defined by the file name.
+    pub fn write(&mut self, event: &Event) -> io::Result<()> {
+        if event.properties.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "event without properties can't be written",
+            ));
+        }
+        check_name(event.name)?;
+        for (name, _) in event.properties {
+            check_name(name)?;
+        }
+        write!(
+            self.inner,
+            "{:02}:{:02}.{:06}-{},{},{}",
+            event.date.minute(),
+            event.date.second(),
+            event.date.nanosecond() / 1000,
+            event.duration.as_micros(),
+            event.name,
+            event.level
+        )?;
+        for (name, value) in event.properties {
+            write!(self.inner, ",{name}=")?;
+            write_value(&mut self.inner, &value.str())?;
+        }
+        self.inner.write_all(b"\r\n")
+    }
+
+    pub fn write_owned(&mut self, event: &OwnedEvent) -> io::Result<()> {
+        event.with_event(|event| self.write(event))
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Names are written as is, the separators of the format would make the record unreadable.
+fn check_name(name: &str) -> io::Result<()> {
+    if name.contains([',', '=', '\r', '\n']) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("name {name:?} can't contain ',', '=' or line breaks"),
+        ));
+    }
+    Ok(())
+}
+
+fn write_value(w: &mut impl Write, value: &str) -> io::Result<()> {
+    let need_quotes = value.starts_with(['\'', '"']) || value.contains([',', '\r', '\n']);
+    if !need_quotes {
+        return w.write_all(value.as_bytes());
+    }
+
+    let quote = if value.contains('\'') && !value.contains('"') {
+        '"'
+    } else {
+        '\''
+    };
+    let mut buf = [0u8; 4];
+    let quote = quote.encode_utf8(&mut buf);
+
+    w.write_all(quote.as_bytes())?;
+    for (i, part) in value.split(&*quote).enumerate() {
+        if i > 0 {
+            w.write_all(quote.as_bytes())?;
+            w.write_all(quote.as_bytes())?;
+        }
+        w.write_all(part.as_bytes())?;
+    }
+    w.write_all(quote.as_bytes())
+}
+
+/// Name of the log file containing events of the hour of `date`, e.g. `24010415.log`.
+pub fn log_file_name(date: &NaiveDateTime) -> String {
+    date.format("%y%m%d%H.log").to_string()
+}
+
+type FileLogWriter = LogWriter<BufWriter<File>>;
+
+/// Writes events into a directory, one file per hour, named as the platform does.
+/// Events don't have to be sorted, an existing file is appended.
+pub struct HourlyLogWriter {
+    dir: PathBuf,
+    current: Option<((NaiveDate, u32), FileLogWriter)>,
+}
+
+impl HourlyLogWriter {
+    pub fn new(dir: impl AsRef<Path>) -> io::Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir: dir.as_ref().to_owned(),
+            current: None,
+        })
+    }
+
+    pub fn write(&mut self, event: &Event) -> io::Result<()> {
+        let hour = (event.date.date(), event.date.hour());
+        let writer = match &mut self.current {
+            Some((current, writer)) if *current == hour => writer,
+            _ => {
+                self.flush()?;
+                let writer = self.open(&event.date)?;
+                &mut self.current.insert((hour, writer)).1
+            }
+        };
+        writer.write(event)
+    }
+
+    pub fn write_owned(&mut self, event: &OwnedEvent) -> io::Result<()> {
+        event.with_event(|event| self.write(event))
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match &mut self.current {
+            Some((_, writer)) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn open(&self, date: &NaiveDateTime) -> io::Result<FileLogWriter> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(log_file_name(date)))?;
+        let is_empty = file.metadata()?.len() == 0;
+        let mut writer = LogWriter::new(BufWriter::new(file));
+        if is_empty {
+            writer.write_bom()?;
+        }
+        Ok(writer)
+    }
+}
+
+impl Drop for HourlyLogWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{NaiveDate, Timelike};
+
+    use super::{LogWriter, log_file_name};
+    use crate::{Event, LogStr, OwnedEvent, Parser, parse_record};
+
+    fn write(name: &str, prop: &str, value: &str) -> std::io::Result<Vec<u8>> {
+        let properties = [
+            ("process", LogStr::new(b"rphost", '\0')),
+            (prop, LogStr::new(value.as_bytes(), '\0')),
+        ];
+        let event = Event {
+            date: NaiveDate::from_ymd_opt(2024, 1, 4)
+                .and_then(|date| date.and_hms_opt(15, 0, 0))
+                .unwrap(),
+            duration: Duration::from_micros(1),
+            name,
+            level: 0,
+            properties: &properties,
+        };
+        let mut writer = LogWriter::new(Vec::new());
+        writer.write(&event)?;
+        Ok(writer.into_inner())
+    }
+
+    fn round_trip(value: &str) -> String {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 4)
+            .and_then(|date| date.and_hms_micro_opt(15, 5, 2, 3))
+            .unwrap();
+        let properties = [
+            ("process", LogStr::new(b"rphost", '\0')),
+            ("Value", LogStr::new(value.as_bytes(), '\0')),
+            ("OSThread", LogStr::new(b"1", '\0')),
+        ];
+        let event = Event {
+            date,
+            duration: Duration::from_micros(42),
+            name: "CALL",
+            level: 3,
+            properties: &properties,
+        };
+
+        let mut writer = LogWriter::new(Vec::new());
+        writer.write(&event).unwrap();
+        let buf = writer.into_inner();
+        assert!(buf.starts_with(b"05:02.000003-42,CALL,3,process=rphost,Value="));
+
+        let mut parser = Parser::new(&buf);
+        let mut parsed = parse_record(&mut parser, date.with_minute(0).unwrap())
+            .unwrap()
+            .to_owned_event();
+        assert_eq!(parser.position(), buf.len());
+        assert_eq!(parsed, OwnedEvent::from(&event));
+        parsed.properties.swap_remove(1).1
+    }
+
+    #[test]
+    fn test_values() {
+        for value in [
+            "",
+            "plain",
+            "a,b",
+            "multi\r\nline",
+            "it's",
+            "say \"hi\"",
+            "'quoted'",
+            "\"both' quotes\",",
+            "ends with '",
+            "ends with ''",
+        ] {
+            assert_eq!(round_trip(value), value);
+        }
+    }
+
+    #[test]
+    fn test_names() {
+        for (name, prop) in [("SCALL", "Имя свойства"), ("t:connectID", "p:processName")]
+        {
+            let buf = write(name, prop, "1").unwrap();
+            let mut parser = Parser::new(&buf);
+            let event = parse_record(&mut parser, NaiveDate::default().into()).unwrap();
+            assert_eq!(event.name, name);
+            assert_eq!(event.properties[1].0, prop);
+        }
+        for (name, prop) in [
+            ("CA,LL", "Value"),
+            ("CALL\r\n", "Value"),
+            ("CALL", "Val=ue"),
+            ("CALL", "Val,ue"),
+            ("CALL", "Value\n"),
+        ] {
+            let err = write(name, prop, "1").unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn test_file_name() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 4)
+            .and_then(|date| date.and_hms_opt(15, 55, 42))
+            .unwrap();
+        assert_eq!(log_file_name(&date), "24010415.log");
+    }
+}
@@ -0,0 +1,64 @@
+//! Tests over buffers in memory, they don't touch the file system and run under Miri too:
+//! `cargo +nightly miri test --test buffers`.
+
+use std::error::Error;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use tech_log_parser::OwnedEvent;
+
+const LOG: &[u8] = include_bytes!("../test-log/24010415.log");
+
+fn log() -> &'static [u8] {
+    LOG.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(LOG)
+}
+
+fn date() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2024, 1, 4)
+        .and_then(|date| date.and_hms_opt(15, 0, 0))
+        .unwrap()
+}
+
+fn parse(buffer: &[u8]) -> Result<(Vec<OwnedEvent>, usize), Box<dyn Error>> {
+    let mut events = Vec::new();
+    let (_, position) = tech_log_parser::parse_buffer(buffer, date(), &mut |event| {
+        events.push(event.to_owned_event());
+        Ok(true)
+    })?;
+    Ok((events, position))
+}
+
+#[test]
+fn test_parse_buffer() -> Result<(), Box<dyn Error>> {
+    let (events, position) = parse(log())?;
+    assert_eq!(events.len(), 48);
+    assert_eq!(position, log().len());
+    assert_eq!(events[0].name, "DBV8DBEng");
+    assert_eq!(events[1].name, "EXCP");
+    assert!(events.iter().all(|x| x.date >= date()));
+    Ok(())
+}
+
+#[test]
+fn test_truncated_buffer() -> Result<(), Box<dyn Error>> {
+    let (all, _) = parse(log())?;
+    // every cut ends inside or at the end of a record, the parsed events are a prefix of all
+    for len in (0..log().len()).step_by(97) {
+        let (events, position) = parse(&log()[..len])?;
+        assert!(position <= len);
+        assert!(events.len() <= all.len());
+        assert_eq!(events[..], all[..events.len()]);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_invalid_records() -> Result<(), Box<dyn Error>> {
+    let buffer = b"57:20.886000-1,EXCP,0,process=rphost\r\n\
+        xx:20.886000-1,EXCP,0,process=rphost\r\n\
+        57:21.000000-2,CALL,1,Usr=\"Admin\",Context=\r\n";
+    let (events, _) = parse(buffer)?;
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].name, "EXCP");
+    assert_eq!(events[1].name, "CALL");
+    Ok(())
+}
@@ -3,7 +3,7 @@
 use std::{error::Error, time::Duration};
 
 use chrono::NaiveDateTime;
-use tech_log_parser::Event;
+use tech_log_parser::{Event, HourlyLogWriter, OwnedEvent};
 
 #[allow(dead_code)]
 struct OwnEvent {
@@ -18,8 +18,7 @@ impl OwnEvent {
     pub fn get_first_prop(&self, name: &str) -> Result<&str, Box<dyn Error>> {
         self.props
             .iter()
-            .filter(|(x, _)| x == name)
-            .next()
+            .find(|(x, _)| x == name)
             .map(|(_, x)| x.as_str())
             .ok_or("".into())
     }
@@ -75,3 +74,66 @@ fn test_parse_file_with_worker() -> Result<(), Box<dyn Error>> {
     assert_eq!(events.len(), 48);
     Ok(())
 }
+
+#[test]
+fn test_write_round_trip() -> Result<(), Box<dyn Error>> {
+    let mut events = Vec::<OwnedEvent>::new();
+    tech_log_parser::parse_file("test-log/24010415.log", &mut |event| {
+        events.push(event.to_owned_event());
+        Ok(true)
+    })?;
+
+    let dir = std::env::temp_dir().join(format!("tech-log-writer-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut writer = HourlyLogWriter::new(&dir)?;
+    for event in events.iter() {
+        writer.write_owned(event)?;
+    }
+    drop(writer);
+
+    let mut written = Vec::<OwnedEvent>::new();
+    tech_log_parser::parse_file(dir.join("24010415.log"), &mut |event| {
+        written.push(event.to_owned_event());
+        Ok(true)
+    })?;
+    std::fs::remove_dir_all(&dir)?;
+
+    assert_eq!(written.len(), 48);
+    assert_eq!(written, events);
+    Ok(())
+}
+
+#[test]
+fn test_parse_file_from() -> Result<(), Box<dyn Error>> {
+    let data = std::fs::read("test-log/24010415.log")?;
+    let dir = std::env::temp_dir().join(format!("tech-log-from-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let file_name = dir.join("24010415.log");
+
+    // the file is still being written, the last record is incomplete
+    std::fs::write(&file_name, &data[..data.len() / 2])?;
+    let mut first = Vec::<OwnedEvent>::new();
+    let offset = tech_log_parser::parse_file_from(&file_name, 0, &mut |event| {
+        first.push(event.to_owned_event());
+        Ok(true)
+    })?;
+    assert!(offset < data.len() as u64 / 2);
+
+    std::fs::write(&file_name, &data)?;
+    let mut rest = Vec::<OwnedEvent>::new();
+    let end = tech_log_parser::parse_file_from(&file_name, offset, &mut |event| {
+        rest.push(event.to_owned_event());
+        Ok(true)
+    })?;
+    std::fs::remove_dir_all(&dir)?;
+
+    let mut all = Vec::<OwnedEvent>::new();
+    tech_log_parser::parse_file("test-log/24010415.log", &mut |event| {
+        all.push(event.to_owned_event());
+        Ok(true)
+    })?;
+    assert_eq!(end, data.len() as u64);
+    first.extend(rest);
+    assert_eq!(first, all);
+    Ok(())
+}
@@ -1,16 +1,20 @@
 use std::{error::Error, time::Instant};
 
+use chrono::NaiveDate;
+
 fn main() -> Result<(), Box<dyn Error>> {
-    let Some(file_name) = std::env::args().nth(1) else {
-        println!("usage: simple-bench /path/to/file/*.log");
+    let mut args = std::env::args().skip(1);
+    let Some(file_name) = args.next() else {
+        println!("usage: simple-bench /path/to/file/*.log [repeat]");
         return Ok(());
     };
+    let repeat: usize = args.next().map(|x| x.parse()).transpose()?.unwrap_or(0);
 
     let mut count: usize = 0;
     let mut max_properies = 0;
 
     let start = Instant::now();
-    tech_log_parser::parse_file_with_worker(file_name, &mut |event| {
+    tech_log_parser::parse_file_with_worker(&file_name, &mut |event| {
         count += 1;
         max_properies = max_properies.max(event.properties.len());
         Ok(true)
@@ -20,5 +24,26 @@ fn main() -> Result<(), Box<dyn Error>> {
     println!("count: {count}");
     println!("max_properies: {max_properies}");
 
+    if repeat > 0 {
+        // parsing of a buffer in memory, without reading the file
+        let buffer = std::fs::read(&file_name)?;
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1)
+            .and_then(|x| x.and_hms_opt(0, 0, 0))
+            .ok_or("invalid date")?;
+        let mut count: usize = 0;
+        let start = Instant::now();
+        for _ in 0..repeat {
+            tech_log_parser::parse_buffer(&buffer, date, &mut |event| {
+                count += event.properties.len();
+                Ok(true)
+            })?;
+        }
+        let elapsed = start.elapsed();
+        let megabytes = (buffer.len() * repeat) as f64 / 1024.0 / 1024.0;
+        println!("parse_buffer x{repeat}: {elapsed:?}");
+        println!("throughput: {:.0} MB/s", megabytes / elapsed.as_secs_f64());
+        println!("properties: {count}");
+    }
+
     Ok(())
 }